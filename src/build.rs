@@ -1,14 +1,26 @@
 use clap::ValueEnum;
-use miette::{IntoDiagnostic, Result, WrapErr};
+use miette::{IntoDiagnostic, NamedSource, Result, WrapErr};
+use serde::Serialize;
+use similar::TextDiff;
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
     fs::{self},
-    path::PathBuf,
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::{Duration, Instant, SystemTime},
 };
 use tinytemplate::TinyTemplate;
 
+pub use crate::formats::STDOUT;
 use crate::{
-    comment_parser, errors::SpecError, formats, git::get_local_repo_path, toml_parser, transformers,
+    comment_parser::{self, SpecBlock},
+    errors::SpecError,
+    filters, formats,
+    git::{get_local_repo_path, SourceLinks},
+    markdown,
+    template::{self, IncludedTemplates},
+    toml_parser::{self, Language, Manifest, OutputConfig, Specification},
 };
 
 /// The different specification format that cargo-spec can output
@@ -19,6 +31,33 @@ pub enum OutputFormat {
 
     /// Respec
     Respec,
+
+    /// Standalone HTML page, that works offline
+    Html,
+
+    /// PDF document (math isn't typeset, it's written as its TeX source,
+    /// and only Latin-1 characters can be rendered)
+    Pdf,
+
+    /// LaTeX document
+    Latex,
+
+    /// Multi-page book: an mdBook project with a page per chapter,
+    /// or a Docusaurus docs folder with the docusaurus flavor
+    Book,
+}
+
+impl OutputFormat {
+    /// The file the specification is written to when no output file is given
+    pub fn default_output_file(self) -> PathBuf {
+        match self {
+            OutputFormat::Markdown => PathBuf::from("specification.md"),
+            OutputFormat::Respec | OutputFormat::Html => PathBuf::from("specification.html"),
+            OutputFormat::Pdf => PathBuf::from("specification.pdf"),
+            OutputFormat::Latex => PathBuf::from("specification.tex"),
+            OutputFormat::Book => PathBuf::from("book"),
+        }
+    }
 }
 
 /// The markdown flavor to use when outputting markdown
@@ -32,24 +71,361 @@ pub enum MarkdownFlavor {
     Docusaurus,
 }
 
-/// Builds the specification and returns a number of files to watch
-pub fn build(
-    toml_spec: PathBuf,
+impl MarkdownFlavor {
+    /// The flavor set by an `[output]` table, if any (unknown flavors are ignored)
+    fn configured(output: Option<&OutputConfig>) -> Option<Self> {
+        output
+            .and_then(|output| output.flavor.as_deref())
+            .and_then(|flavor| Self::from_str(flavor, true).ok())
+    }
+}
+
+/// The content of a specification, in a given output format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    /// A single file
+    File(Vec<u8>),
+    /// Several files, with their paths relative to the output directory
+    Directory(Vec<(PathBuf, String)>),
+}
+
+/// A specification built in memory
+pub struct Rendered {
+    /// The file (or directory) the specification is written to
+    pub output_file: PathBuf,
+    /// The content of the specification, in the requested output format
+    pub content: Content,
+    /// The files the specification was built from
+    pub files_to_watch: HashSet<PathBuf>,
+    /// How the source links of the specification are written, if it has any
+    pub(crate) source_links: Option<SourceLinks>,
+    /// The problems that don't prevent building the specification, like unused sections
+    pub warnings: Vec<miette::Report>,
+}
+
+/// What the template is rendered with
+#[derive(Serialize)]
+struct Context<'a> {
+    #[serde(flatten)]
+    specification: &'a Specification,
+    /// the code blocks embedded with `{code "path" "Item"}`
+    code: Vec<String>,
+}
+
+/// The spec comments extracted from files, shared by the specifications of a specification file
+/// (the same file is often listed by several of them), and by the rebuilds of the watch mode
+/// (where only the files that changed since are extracted again)
+#[derive(Default)]
+pub struct ExtractionCache {
+    blocks: HashMap<(PathBuf, Option<Language>), (FileVersion, Vec<SpecBlock>)>,
+}
+
+/// What tells if a file changed: its modification time and size
+type FileVersion = Option<(SystemTime, u64)>;
+
+impl ExtractionCache {
+    /// Extracts the spec comments of a file, unless they were already extracted
+    /// with the same comment syntax and the file didn't change since
+    fn parse_file(
+        &mut self,
+        path: &Path,
+        languages: &HashMap<String, Language>,
+    ) -> Result<Vec<SpecBlock>> {
+        let language = path
+            .extension()
+            .and_then(|extension| languages.get(extension.to_str()?))
+            .cloned();
+        let key = (path.to_path_buf(), language);
+        let version = fs::metadata(path)
+            .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+            .ok();
+
+        // files whose version can't be read are always extracted again
+        if let Some((cached, blocks)) = self.blocks.get(&key) {
+            if version.is_some() && *cached == version {
+                return Ok(blocks.clone());
+            }
+        }
+
+        let blocks = comment_parser::parse_file(path, languages)?;
+        self.blocks.insert(key, (version, blocks.clone()));
+        Ok(blocks)
+    }
+
+    /// Forgets the spec comments extracted from files that changed (or from the files of
+    /// directories that changed), since a file can change without a new modification time
+    /// or size (when it's saved twice within the resolution of the file system's clock)
+    pub fn invalidate(&mut self, changed: &BTreeSet<PathBuf>) {
+        let changed: Vec<_> = changed
+            .iter()
+            .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+            .collect();
+        self.blocks.retain(|(path, _), _| {
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            !changed.iter().any(|changed| path.starts_with(changed))
+        });
+    }
+}
+
+/// Where a specification is defined, to point at its sections in diagnostics
+struct SpecSource<'a> {
+    /// the specification file
+    path: &'a Path,
+    /// the content of the specification file
+    content: &'a str,
+    /// where the sections of the specification are defined in the file
+    sections: HashMap<String, (usize, usize)>,
+}
+
+/// Builds all the specifications of a specification file in memory,
+/// without writing them anywhere, with the given flavor or the ones of their `[output]` tables
+pub fn render_all(
+    toml_spec: &Path,
     output_file: Option<PathBuf>,
     output_format: OutputFormat,
-    flavor: MarkdownFlavor,
-) -> Result<HashSet<PathBuf>> {
-    let mut files_to_watch = HashSet::new();
+    flavor: Option<MarkdownFlavor>,
+) -> Result<Vec<Rendered>> {
+    let mut cache = ExtractionCache::default();
+    render_all_with(toml_spec, output_file, output_format, flavor, &mut cache)
+}
 
-    //~ 1. parse the specification file with the [toml_parser](#toml-parser)
-    let mut specification = toml_parser::parse_toml_spec(toml_spec.as_path())?;
+/// Like [render_all], reusing the spec comments extracted by previous builds
+pub fn render_all_with(
+    toml_spec: &Path,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    flavor: Option<MarkdownFlavor>,
+    cache: &mut ExtractionCache,
+) -> Result<Vec<Rendered>> {
+    //~ 1. parse the specification file with the [toml_parser](#toml-parser).
+    //~    It can list several specifications with `[[spec]]`, each of them going through
+    //~    the following steps (a file listed by several of them is only parsed once)
+    let manifest = toml_parser::parse_manifest(toml_spec)?;
+    let content = toml_parser::read_spec_file(toml_spec)?;
+    let mut section_spans = toml_parser::section_spans(&content).into_iter();
 
-    let mut spec_dir =
-        fs::canonicalize(&toml_spec).expect("couldn't canonicalize the specification path");
+    let mut spec_dir = fs::canonicalize(toml_spec)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            format!(
+                "could not find the specification file {}",
+                toml_spec.display()
+            )
+        })?;
     spec_dir.pop();
 
+    //~    (the markdown flavor comes from the command line, or the `[output]` table
+    //~    of the specification, or the one shared by all the specifications)
+    let specs = match manifest {
+        Manifest::Single(specification) => {
+            let output_file = output_file.unwrap_or_else(|| output_format.default_output_file());
+            let flavor = flavor
+                .or_else(|| MarkdownFlavor::configured(specification.output.as_ref()))
+                .unwrap_or_default();
+            vec![(*specification, output_file, flavor)]
+        }
+        Manifest::Multiple(entries, shared_output) => {
+            if let Some(output_file) = output_file {
+                return Err(SpecError::SeveralSpecsOutputFile(output_file).into());
+            }
+
+            let mut output_files = HashSet::new();
+            let mut specs = vec![];
+            for entry in entries {
+                // each specification is written next to the specification file,
+                // after its name by default
+                let output_file = match &entry.output_file {
+                    Some(output_file) => spec_dir.join(output_file),
+                    None => {
+                        let default = output_format.default_output_file();
+                        let name = markdown::slugify(&entry.specification.metadata.name);
+                        match default.extension() {
+                            Some(extension) => spec_dir.join(name).with_extension(extension),
+                            None => spec_dir.join(name),
+                        }
+                    }
+                };
+                if !output_files.insert(output_file.clone()) {
+                    return Err(SpecError::DuplicateOutputFile(output_file).into());
+                }
+                let flavor = flavor
+                    .or_else(|| MarkdownFlavor::configured(entry.specification.output.as_ref()))
+                    .or_else(|| MarkdownFlavor::configured(shared_output.as_ref()))
+                    .unwrap_or_default();
+                specs.push((entry.specification, output_file, flavor));
+            }
+            specs
+        }
+    };
+
+    specs
+        .into_iter()
+        .map(|(specification, output_file, flavor)| {
+            let source = SpecSource {
+                path: toml_spec,
+                content: &content,
+                sections: section_spans.next().unwrap_or_default(),
+            };
+            render(
+                specification,
+                output_file,
+                source,
+                &spec_dir,
+                cache,
+                output_format,
+                flavor,
+            )
+        })
+        .collect()
+}
+
+/// Builds a specification in memory, to use cargo-spec as a library
+/// (from a `build.rs` script, for example)
+///
+/// ```no_run
+/// use cargo_spec::{Content, OutputFormat, SpecBuilder};
+///
+/// let builder = SpecBuilder::from_file("Specification.toml")?.output_format(OutputFormat::Html);
+/// if let Content::File(html) = builder.render()? {
+///     std::fs::write("specification.html", html).unwrap();
+/// }
+/// # Ok::<(), miette::Report>(())
+/// ```
+pub struct SpecBuilder {
+    specification: Specification,
+    /// the directory the paths of the specification are relative to
+    spec_dir: PathBuf,
+    /// the specification file (and its content), that diagnostics point at
+    source: (PathBuf, String),
+    output_format: OutputFormat,
+    flavor: Option<MarkdownFlavor>,
+}
+
+impl SpecBuilder {
+    /// Starts building a specification, whose template and sections are relative to `spec_dir`
+    pub fn new(specification: Specification, spec_dir: impl Into<PathBuf>) -> Self {
+        // diagnostics point at the specification as it would be written in a file
+        let content = toml::to_string(&specification).unwrap_or_default();
+        Self {
+            specification,
+            spec_dir: spec_dir.into(),
+            source: (PathBuf::from("<specification>"), content),
+            output_format: OutputFormat::Markdown,
+            flavor: None,
+        }
+    }
+
+    /// Starts building the specification of a specification file
+    /// (the specifications of a file listing several of them with `[[spec]]`
+    /// can be built with [SpecBuilder::new] and [toml_parser::parse_manifest])
+    pub fn from_file(toml_spec: impl AsRef<Path>) -> Result<Self> {
+        let toml_spec = toml_spec.as_ref();
+        let Manifest::Single(specification) = toml_parser::parse_manifest(toml_spec)? else {
+            return Err(SpecError::SeveralSpecs(toml_spec.to_path_buf()).into());
+        };
+        let content = toml_parser::read_spec_file(toml_spec)?;
+
+        let mut spec_dir = fs::canonicalize(toml_spec).into_diagnostic()?;
+        spec_dir.pop();
+
+        Ok(Self {
+            specification: *specification,
+            spec_dir,
+            source: (toml_spec.to_path_buf(), content),
+            output_format: OutputFormat::Markdown,
+            flavor: None,
+        })
+    }
+
+    /// The output format (defaults to markdown)
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// The markdown flavor (defaults to the one of the `[output]` table of the specification)
+    pub fn flavor(mut self, flavor: MarkdownFlavor) -> Self {
+        self.flavor = Some(flavor);
+        self
+    }
+
+    /// The specification being built
+    pub fn specification(&self) -> &Specification {
+        &self.specification
+    }
+
+    /// Renders the specification, without writing it anywhere
+    /// (ignoring warnings, like unused sections, that [SpecBuilder::render_with_warnings] returns)
+    pub fn render(&self) -> Result<Content> {
+        self.render_with_warnings().map(|(content, _)| content)
+    }
+
+    /// Renders the specification, along with the problems that don't prevent rendering it
+    /// (like sections that the template doesn't use)
+    pub fn render_with_warnings(&self) -> Result<(Content, Vec<miette::Report>)> {
+        let spec_dir = fs::canonicalize(&self.spec_dir)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                format!(
+                    "could not find the specification directory {}",
+                    self.spec_dir.display()
+                )
+            })?;
+
+        let (path, content) = &self.source;
+        let source = SpecSource {
+            path,
+            content,
+            sections: toml_parser::section_spans(content)
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+        };
+
+        let flavor = self
+            .flavor
+            .or_else(|| MarkdownFlavor::configured(self.specification.output.as_ref()))
+            .unwrap_or_default();
+
+        let rendered = render(
+            self.specification.clone(),
+            self.output_format.default_output_file(),
+            source,
+            &spec_dir,
+            &mut ExtractionCache::default(),
+            self.output_format,
+            flavor,
+        )?;
+        Ok((rendered.content, rendered.warnings))
+    }
+
+    /// Renders the specification and writes it to `writer`
+    /// (the book format, made of several files, can only be rendered with [SpecBuilder::render])
+    pub fn write_to(&self, mut writer: impl io::Write) -> Result<()> {
+        formats::write_to(&self.render()?, &mut writer)?;
+        Ok(())
+    }
+
+    /// Extracts the spec comments of a file, relative to the specification directory
+    pub fn extract(&self, path: impl AsRef<Path>) -> Result<Vec<SpecBlock>> {
+        comment_parser::parse_file(&self.spec_dir.join(path), &self.specification.languages)
+    }
+}
+
+/// Builds a specification in memory
+fn render(
+    mut specification: Specification,
+    output_file: PathBuf,
+    source: SpecSource,
+    spec_dir: &Path,
+    cache: &mut ExtractionCache,
+    output_format: OutputFormat,
+    flavor: MarkdownFlavor,
+) -> Result<Rendered> {
+    let mut files_to_watch = HashSet::new();
+
     //~ 2. retrieve the template file
-    let mut template_path = spec_dir.clone();
+    let mut template_path = spec_dir.to_path_buf();
     template_path.push(&specification.config.template);
     files_to_watch.insert(template_path.clone());
 
@@ -57,123 +433,856 @@ pub fn build(
         .into_diagnostic()
         .wrap_err_with(|| format!("could not read template {}", template_path.display(),))?;
 
-    //~ 3. extract the spec comments from all the files listed using [comment_parser](#comment-parser)
+    //~    (inlining the templates it includes with `{include "path/to/file.md"}`,
+    //~    relative to the template including them)
+    let included = template::include_templates(&template, &template_path)?;
+
+    //~    and make sure that the sections it uses are all defined
+    //~    (the sections it doesn't use are reported as warnings)
+    let mut templates = vec![(template_path.as_path(), template.as_str())];
+    templates.extend(
+        included
+            .files
+            .iter()
+            .map(|(path, content)| (path.as_path(), content.as_str())),
+    );
+    let warnings = check_sections(&specification, &templates, &source)?;
+    files_to_watch.extend(included.files.iter().map(|(file, _)| file.clone()));
+
+    //~ 3. extract the spec comments from all the files listed using [comment_parser](#comment-parser),
+    //~    optionally following each extracted block with a link to its source
+    let source_links = specification
+        .config
+        .source_links
+        .as_deref()
+        .map(|config| SourceLinks::new(config, spec_dir))
+        .transpose()?;
     let base = get_local_repo_path();
-    for filename in specification.sections.values_mut() {
-        let path = if matches!(filename.chars().next(), Some('@')) {
-            let base = base
-                .as_ref()
-                .ok_or(SpecError::NotGitRepo(filename.clone()))
-                .into_diagnostic()?;
-            let base = base.trim();
-            // TODO: this will panic if we just wrote @ and not @/
-            let filename = filename.split_at(2).1.to_string();
-            PathBuf::from(base).join(filename)
-        } else {
-            let mut path = spec_dir.clone();
-            path.push(&filename);
-            path
+    for (section, filename) in specification.sections.iter_mut() {
+        let (root, relative) = split_root(filename, spec_dir, base.as_deref())?;
+
+        //~    (a section can list a single file, a directory, or a glob pattern like `src/**/*.rs`,
+        //~    optionally followed by `#name` to only extract the blocks within `spec:begin(name)`)
+        let (relative, anchor) = match relative.rsplit_once('#') {
+            Some((relative, anchor)) => (relative.to_string(), Some(anchor)),
+            None => (relative, None),
         };
-        files_to_watch.insert(path.clone());
+        let files = section_files(section, &root, &relative, &specification.languages)?;
+        files_to_watch.insert(files.watch);
+
+        let mut content = String::new();
+        let mut found_anchor = false;
+        for path in &files.files {
+            let mut blocks = cache.parse_file(path, &specification.languages)?;
+            if let Some(anchor) = anchor {
+                blocks.retain(|block| block.anchors.iter().any(|name| name == anchor));
+                found_anchor |= !blocks.is_empty();
+            }
+
+            let extracted = match &source_links {
+                Some(source_links) => link_blocks(&blocks, path, source_links),
+                None => comment_parser::join_blocks(&blocks),
+            };
+
+            //~    (when a section lists several files, each file gets its own header)
+            if !files.multiple {
+                content = extracted;
+            } else if !extracted.is_empty() {
+                let header = relative_display(path, &root);
+                content.push_str(&format!("**`{header}`**\n\n{extracted}\n"));
+            }
+        }
 
-        *filename = comment_parser::parse_file(&path)?;
+        if let Some(anchor) = anchor.filter(|_| !found_anchor) {
+            return Err(SpecError::MissingAnchor(section.clone(), anchor.to_string()).into());
+        }
+
+        *filename = content;
     }
 
-    //~ 4. render the template
+    //~ 4. embed the definition of the Rust items referenced in the template
+    //~    with `{code "path/to/file.rs" "ItemName"}`, without requiring any spec comment
+    let embedded = template::embed_code(&included, &specification.languages, |path| {
+        let (root, relative) = split_root(path, spec_dir, base.as_deref())?;
+        Ok(root.join(relative))
+    })?;
+    files_to_watch.extend(embedded.files);
+
+    //~ 5. render the template
     let mut tt = TinyTemplate::new();
     tt.set_default_formatter(&tinytemplate::format_unescaped);
-    tt.add_template("specification", &template)
-        .into_diagnostic()
-        .wrap_err_with(|| format!("can't parse template {}", template_path.display(),))?;
+    //~    (with the filters used in the template, as in `{sections.foo | shift_headings 1}`)
+    //~    (errors point at the template file they come from, even when it's included)
+    filters::register(&mut tt, &embedded.template, &included)?;
+    tt.add_template("specification", &embedded.template)
+        .map_err(|e| template_error(e, &embedded.template, &included, &template_path))?;
 
     let rendered = tt
-        .render("specification", &specification)
-        .into_diagnostic()
-        .wrap_err_with(|| {
-            format!(
-                "template file can't be rendered: {}",
-                template_path.display()
-            )
-        })?;
+        .render(
+            "specification",
+            &Context {
+                specification: &specification,
+                code: embedded.code,
+            },
+        )
+        .map_err(|e| template_error(e, &embedded.template, &included, &template_path))?;
 
-    //~ 5. build the spec. We currently support two different formats:
+    //~ 6. build the spec. We currently support six different formats:
     use OutputFormat::*;
-    match output_format {
+    let content = match output_format {
         //~     - [markdown](https://daringfireball.net/projects/markdown/)
-        Markdown => {
-            let transformed = transformers::get_transformer(flavor).transform(&rendered);
-            formats::markdown::build(&transformed, output_file);
-        }
+        Markdown => Content::File(formats::markdown::build(&rendered, flavor).into_bytes()),
         //~     - [respec](https://github.com/w3c/respec/)
-        Respec => {
-            formats::respec::build(&specification, &rendered, output_file);
+        Respec => Content::File(formats::respec::build(&specification, &rendered)?.into_bytes()),
+        //~     - standalone HTML, with inlined CSS, highlighted code and a table of contents
+        Html => Content::File(formats::html::build(&specification, &rendered)?.into_bytes()),
+        //~     - PDF, rendered without any external tool (with math left as TeX source)
+        Pdf => Content::File(formats::pdf::build(&specification, &rendered)?),
+        //~     - [LaTeX](https://www.latex-project.org/), to publish the spec as a paper
+        Latex => Content::File(formats::latex::build(&specification, &rendered).into_bytes()),
+        //~     - a multi-page [mdBook](https://rust-lang.github.io/mdBook/) project
+        //~       (or [Docusaurus](https://docusaurus.io/) docs folder, with the docusaurus flavor),
+        //~       split at the top-level headings of the markdown
+        Book => {
+            let markdown = formats::markdown::build(&rendered, flavor);
+            Content::Directory(match flavor {
+                MarkdownFlavor::Mdbook => formats::book::mdbook(&specification, &markdown),
+                MarkdownFlavor::Docusaurus => formats::book::docusaurus(&specification, &markdown),
+            })
+        }
+    };
+
+    Ok(Rendered {
+        output_file,
+        content,
+        files_to_watch,
+        source_links,
+        warnings,
+    })
+}
+
+/// Points a tinytemplate error at the template file it comes from,
+/// given the template tinytemplate parsed, made of the `included` templates
+fn template_error(
+    error: tinytemplate::error::Error,
+    template: &str,
+    included: &IncludedTemplates,
+    template_path: &Path,
+) -> miette::Report {
+    use tinytemplate::error::Error::*;
+    let source = |line, column| included.source((template::offset_at(template, line, column), 1));
+    match error {
+        ParseError { msg, line, column } => {
+            let (_src, _bad_bit) = source(line, column);
+            SpecError::BadTemplate {
+                _src,
+                _bad_bit,
+                reason: msg,
+            }
+            .into()
+        }
+        RenderError { msg, line, column } => {
+            let (_src, _bad_bit) = source(line, column);
+            SpecError::CantRenderTemplate {
+                _src,
+                _bad_bit,
+                reason: msg,
+            }
+            .into()
         }
+        CalledTemplateError {
+            err, line, column, ..
+        }
+        | CalledFormatterError {
+            err, line, column, ..
+        } => {
+            let (_src, _bad_bit) = source(line, column);
+            SpecError::CantRenderTemplate {
+                _src,
+                _bad_bit,
+                reason: err.to_string(),
+            }
+            .into()
+        }
+        error => miette::Report::msg(error.to_string()).wrap_err(format!(
+            "template file can't be rendered: {}",
+            template_path.display()
+        )),
+    }
+}
+
+/// What a build wrote, and what it was built from
+pub struct Built {
+    /// The files (or directories) the specifications were written to
+    pub output_files: Vec<PathBuf>,
+    /// The files the specifications were built from
+    pub files_to_watch: HashSet<PathBuf>,
+    /// The problems that don't prevent building the specifications, like unused sections
+    pub warnings: Vec<miette::Report>,
+}
+
+/// Builds the specifications and returns a number of files to watch
+pub fn build(
+    toml_spec: PathBuf,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    flavor: Option<MarkdownFlavor>,
+) -> Result<Built> {
+    let mut cache = ExtractionCache::default();
+    build_with(toml_spec, output_file, output_format, flavor, &mut cache)
+}
+
+/// Like [build], reusing the spec comments extracted by previous builds
+fn build_with(
+    toml_spec: PathBuf,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    flavor: Option<MarkdownFlavor>,
+    cache: &mut ExtractionCache,
+) -> Result<Built> {
+    let mut built = Built {
+        output_files: vec![],
+        files_to_watch: HashSet::new(),
+        warnings: vec![],
     };
 
+    for rendered in render_all_with(&toml_spec, output_file, output_format, flavor, cache)? {
+        //~ 7. write the spec to the output file (or directory), or to stdout if it's `-`
+        formats::write(&rendered.content, &rendered.output_file)?;
+
+        built.output_files.push(rendered.output_file);
+        built.files_to_watch.extend(rendered.files_to_watch);
+        built.warnings.extend(rendered.warnings);
+    }
+
     // return a number of files to watch (useful for the [watch] function)
-    Ok(files_to_watch)
+    Ok(built)
+}
+
+/// What checking the specifications found
+pub struct Checked {
+    /// The files (or directories) the specifications are written to,
+    /// with how they differ from the rebuilt specifications (nothing if they are up to date)
+    pub output_files: Vec<(PathBuf, Vec<Difference>)>,
+    /// The problems that don't prevent building the specifications, like unused sections
+    pub warnings: Vec<miette::Report>,
+}
+
+impl Checked {
+    /// Returns an error if a specification is not up to date
+    pub fn up_to_date(&self) -> Result<()> {
+        let outdated = self
+            .output_files
+            .iter()
+            .find(|(_, differences)| !differences.is_empty());
+        match outdated {
+            Some((output_file, _)) => Err(SpecError::OutdatedSpec(output_file.clone()).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How an output file differs from the rebuilt specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// A file of an output directory that the rebuilt specification doesn't generate anymore
+    Stale(PathBuf),
+    /// A binary file (like a PDF document), which can't be diffed line by line
+    Binary(PathBuf),
+    /// A text file, with the unified diff between it and the rebuilt specification
+    Diff(PathBuf, String),
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difference::Stale(path) => write!(
+                f,
+                "=> {} is not part of the rebuilt specification anymore",
+                path.display()
+            ),
+            Difference::Binary(path) => write!(
+                f,
+                "=> {} differs from the rebuilt specification (binary output)",
+                path.display()
+            ),
+            Difference::Diff(_, diff) => f.write_str(diff.strip_suffix('\n').unwrap_or(diff)),
+        }
+    }
+}
+
+/// Builds the specifications in memory and compares them with their output files,
+/// returning how the ones that are not up to date differ
+pub fn check(
+    toml_spec: PathBuf,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    flavor: Option<MarkdownFlavor>,
+) -> Result<Checked> {
+    // there's no file to compare the specification with
+    if output_file.as_deref() == Some(Path::new(formats::STDOUT)) {
+        return Err(SpecError::StdoutOutputFile("check").into());
+    }
+
+    let mut checked = Checked {
+        output_files: vec![],
+        warnings: vec![],
+    };
+    for Rendered {
+        output_file,
+        content,
+        source_links,
+        warnings,
+        ..
+    } in render_all(&toml_spec, output_file, output_format, flavor)?
+    {
+        let differences = differences(&content, &output_file, source_links.as_ref())?;
+        checked.output_files.push((output_file, differences));
+        checked.warnings.extend(warnings);
+    }
+
+    Ok(checked)
+}
+
+/// Compares a specification with its output file, listing how they differ
+/// (as unified diffs, or only that they differ for binary files,
+/// and the files left over in output directories).
+/// Source links pinned to another commit than the current one don't make it outdated,
+/// since committing the rebuilt specification changes the current commit.
+fn differences(
+    content: &Content,
+    output_file: &Path,
+    source_links: Option<&SourceLinks>,
+) -> Result<Vec<Difference>> {
+    let files = match content {
+        Content::File(content) => vec![(output_file.to_path_buf(), content.as_slice())],
+        Content::Directory(files) => files
+            .iter()
+            .map(|(path, content)| (output_file.join(path), content.as_bytes()))
+            .collect(),
+    };
+
+    let mut differences = vec![];
+    if let Content::Directory(files) = content {
+        differences.extend(
+            formats::stale_files(files, output_file)
+                .into_iter()
+                .map(Difference::Stale),
+        );
+    }
+
+    for (path, content) in files {
+        let existing = fs::read(&path).into_diagnostic().wrap_err_with(|| {
+            format!(
+                "could not read the specification file {}, run `cargo spec build` first",
+                path.display()
+            )
+        })?;
+
+        if existing == content {
+            continue;
+        }
+
+        // binary outputs (like PDF documents) can't be diffed line by line
+        let name = path.display().to_string();
+        let (Ok(existing), Ok(content)) =
+            (std::str::from_utf8(&existing), std::str::from_utf8(content))
+        else {
+            differences.push(Difference::Binary(path));
+            continue;
+        };
+
+        if let Some(source_links) = source_links {
+            if source_links.unpin(existing) == source_links.unpin(content) {
+                continue;
+            }
+        }
+
+        let diff = TextDiff::from_lines(existing, content)
+            .unified_diff()
+            .header(&name, &format!("{name} (rebuilt)"))
+            .to_string();
+        differences.push(Difference::Diff(path, diff));
+    }
+
+    Ok(differences)
+}
+
+/// Makes sure that the sections used by the templates are defined,
+/// returning warnings for the sections that are never used
+fn check_sections(
+    specification: &Specification,
+    templates: &[(&Path, &str)],
+    source: &SpecSource,
+) -> Result<Vec<miette::Report>> {
+    let mut used = HashSet::new();
+    for (path, template) in templates {
+        for (name, offset) in template::placeholders(template) {
+            if !specification.sections.contains_key(name) {
+                return Err(SpecError::MissingSection {
+                    _src: NamedSource::new(path.to_string_lossy(), template.to_string()),
+                    _bad_bit: (offset, name.len()),
+                    name: name.to_string(),
+                }
+                .into());
+            }
+            used.insert(name);
+        }
+    }
+
+    let mut unused: Vec<_> = specification
+        .sections
+        .keys()
+        .filter(|name| !used.contains(name.as_str()))
+        .collect();
+    unused.sort_by_key(|name| source.sections.get(*name));
+    let warnings = unused
+        .into_iter()
+        .map(|name| {
+            miette::Report::new(SpecError::UnusedSection {
+                _src: NamedSource::new(source.path.to_string_lossy(), source.content.to_string()),
+                _bad_bit: source.sections.get(name).copied().unwrap_or_default(),
+                name: name.clone(),
+            })
+        })
+        .collect();
+
+    Ok(warnings)
+}
+
+/// Splits a path of the specification file into a root and a path relative to it:
+/// the root of the repository for paths starting with `@/`, the specification directory otherwise
+pub(crate) fn split_root(
+    path: &str,
+    spec_dir: &Path,
+    base: Option<&str>,
+) -> Result<(PathBuf, String)> {
+    if matches!(path.chars().next(), Some('@')) {
+        let base = base.ok_or(SpecError::NotGitRepo(path.to_string()))?;
+        let base = base.trim();
+        // TODO: this will panic if we just wrote @ and not @/
+        let path = path.split_at(2).1.to_string();
+        Ok((PathBuf::from(base), path))
+    } else {
+        Ok((spec_dir.to_path_buf(), path.to_string()))
+    }
+}
+
+/// Characters that turn the path of a section into a glob pattern
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// The files listed by a section
+pub(crate) struct SectionFiles {
+    /// the files to extract spec comments from, in a deterministic order
+    pub(crate) files: Vec<PathBuf>,
+    /// the path to watch in order to pick up changes (and new files) of the section
+    watch: PathBuf,
+    /// whether the section is a directory or a glob pattern (as opposed to a single file)
+    multiple: bool,
+}
+
+/// Lists the files of a section, whose `relative` path (to `root`)
+/// is either a single file, a directory, or a glob pattern
+/// (only the files of a directory whose extension is supported are listed)
+pub(crate) fn section_files(
+    section: &str,
+    root: &Path,
+    relative: &str,
+    languages: &HashMap<String, Language>,
+) -> Result<SectionFiles> {
+    let path = root.join(relative);
+
+    let (mut files, watch) = if path.is_dir() {
+        let mut files = vec![];
+        walk_dir(&path, &mut files)?;
+        // other files (like images, or a Makefile) can't contain spec comments
+        files.retain(|file| comment_parser::is_supported(file, languages));
+        (files, path)
+    } else if relative.contains(GLOB_CHARS) {
+        let pattern = path.to_string_lossy();
+        let mut files = vec![];
+        for entry in glob::glob(&pattern).map_err(|_| SpecError::BadGlob(relative.to_string()))? {
+            let entry = entry.into_diagnostic()?;
+            if entry.is_file() {
+                files.push(entry);
+            }
+        }
+
+        // watch the deepest directory that doesn't contain any pattern
+        let mut watch = root.to_path_buf();
+        for component in Path::new(relative).components() {
+            if component.as_os_str().to_string_lossy().contains(GLOB_CHARS) {
+                break;
+            }
+            watch.push(component);
+        }
+
+        (files, watch)
+    } else {
+        return Ok(SectionFiles {
+            files: vec![path.clone()],
+            watch: path,
+            multiple: false,
+        });
+    };
+
+    if files.is_empty() {
+        return Err(SpecError::NoMatchingFiles(section.to_string(), relative.to_string()).into());
+    }
+
+    files.sort();
+
+    Ok(SectionFiles {
+        files,
+        watch,
+        multiple: true,
+    })
+}
+
+/// Recursively lists the files of a directory, ignoring hidden files and directories
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("could not read directory {}", dir.display()))?;
+
+    for entry in entries {
+        let entry = entry.into_diagnostic()?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        if path.is_dir() {
+            walk_dir(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
 }
 
+/// Displays a path relative to `root`, using `/` as separator
+pub(crate) fn relative_display(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Concatenates extracted blocks, following each one with a link to its source
+fn link_blocks(blocks: &[SpecBlock], path: &Path, source_links: &SourceLinks) -> String {
+    let mut result = String::new();
+    for block in blocks {
+        result.push_str(&block.content);
+        if let Some(url) = source_links.url(path, block.line) {
+            result.push_str(&format!("\n[source]({url})\n\n"));
+        }
+    }
+    result
+}
+
+/// Builds the specifications on every change of the files they are built from,
+/// only returning if the files can't be watched anymore
 pub fn watch(
     toml_spec: PathBuf,
     output_format: OutputFormat,
     output_file: Option<PathBuf>,
-    flavor: MarkdownFlavor,
-) {
+    flavor: Option<MarkdownFlavor>,
+) -> Result<()> {
+    // the specification would be mixed with the rebuild summaries
+    if output_file.as_deref() == Some(Path::new(formats::STDOUT)) {
+        return Err(SpecError::StdoutOutputFile("watch").into());
+    }
+
+    let mut cache = ExtractionCache::default();
+    watch_with(&toml_spec, |changed| {
+        cache.invalidate(changed);
+        let built = build_with(
+            toml_spec.clone(),
+            output_file.clone(),
+            output_format,
+            flavor,
+            &mut cache,
+        )?;
+        for output_file in &built.output_files {
+            println!("\n=> specification saved at {}", output_file.display());
+        }
+        Ok(built)
+    })
+}
+
+/// How long the watch mode waits for changes to settle before rebuilding
+/// (saving a file in an editor usually makes several changes in a row)
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Calls `rebuild` on every change of the specification file, or of the files
+/// the last successful call returned, with the paths that changed since the previous call
+/// (none for the first one), only returning if they can't be watched anymore
+pub fn watch_with(
+    toml_spec: &Path,
+    mut rebuild: impl FnMut(&BTreeSet<PathBuf>) -> Result<Built>,
+) -> Result<()> {
     use notify::{recommended_watcher, RecursiveMode, Watcher};
-    use std::sync::mpsc::channel;
 
     // Create a channel to receive the events.
     let (tx, rx) = channel();
 
     // Create a watcher object, delivering debounced events.
     // The notification back-end is selected based on the platform.
-    let mut watcher =
-        recommended_watcher(move |res| tx.send(res).expect("failed to send event")).unwrap();
+    // (events sent once we stopped listening can be dropped)
+    let mut watcher = recommended_watcher(move |res| {
+        let _ = tx.send(res);
+    })
+    .map_err(|e| SpecError::CantWatch(toml_spec.to_path_buf(), e))?;
     watcher
-        .watch(toml_spec.as_path(), RecursiveMode::NonRecursive)
-        .unwrap_or_else(|_e| {
-            panic!(
-                "could not watch specification file: {}",
-                toml_spec.display()
-            )
-        });
+        .watch(toml_spec, RecursiveMode::NonRecursive)
+        .map_err(|e| SpecError::CantWatch(toml_spec.to_path_buf(), e))?;
+
+    // we don't want to rebuild when we write the specifications
+    // (this can happen when watching a directory containing them)
+    let mut output_files = vec![];
 
     let mut files_to_watch = HashSet::new();
 
+    // changes are listed relative to the specification file
+    let spec_dir = fs::canonicalize(toml_spec)
+        .ok()
+        .and_then(|path| Some(path.parent()?.to_path_buf()))
+        .unwrap_or_default();
+    let mut changed = BTreeSet::new();
+
     loop {
         // build and get files to watch
-        match build(
-            toml_spec.clone(),
-            output_file.clone(),
-            output_format,
-            flavor,
-        ) {
-            Err(e) => println!("error: {}", e),
-            Ok(new_files_to_watch) => {
+        let start = Instant::now();
+        let result = rebuild(&changed);
+        let elapsed = start.elapsed().as_millis();
+        let changes = if changed.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " after changes to {}",
+                summarize_changes(&changed, &spec_dir)
+            )
+        };
+
+        match result {
+            Err(e) => {
+                eprintln!("{:?}", e);
+                println!("=> build failed{changes} ({elapsed}ms)");
+            }
+            Ok(Built {
+                output_files: new_output_files,
+                files_to_watch: new_files_to_watch,
+                warnings,
+            }) => {
+                for warning in warnings {
+                    eprintln!("{warning:?}");
+                }
+
                 // watch any new files contained in the specification
+                // (a file that can't be watched is reported, and picked up by the next build)
+                let mut watched = HashSet::new();
                 for file in new_files_to_watch.difference(&files_to_watch) {
-                    watcher
-                        .watch(file.as_path(), RecursiveMode::NonRecursive)
-                        .unwrap_or_else(|_e| {
-                            panic!("could not find file to watch {}", file.display())
-                        });
+                    // directories are watched recursively to pick up new files
+                    let mode = if file.is_dir() {
+                        RecursiveMode::Recursive
+                    } else {
+                        RecursiveMode::NonRecursive
+                    };
+                    match watcher.watch(file.as_path(), mode) {
+                        Ok(()) => {
+                            watched.insert(file.clone());
+                        }
+                        Err(e) => {
+                            let error = SpecError::CantWatch(file.clone(), e);
+                            eprintln!("{:?}", miette::Report::new(error));
+                        }
+                    }
                 }
 
                 // unwatch files that are not in the specification
+                // (this fails for files that were removed, which are not watched anymore anyway)
                 for file in files_to_watch.difference(&new_files_to_watch) {
-                    watcher.unwatch(file.as_path()).unwrap_or_else(|_e| {
-                        panic!("could not find file to watch {}", file.display())
-                    });
+                    let _ = watcher.unwatch(file.as_path());
                 }
 
-                files_to_watch = new_files_to_watch;
+                files_to_watch.retain(|file| new_files_to_watch.contains(file));
+                files_to_watch.extend(watched);
+                output_files = new_output_files;
+
+                println!("=> built{changes} ({elapsed}ms)");
             }
         };
 
-        match rx.recv() {
-            Ok(event) => println!("{:?}", event),
-            Err(e) => panic!("watch error: {:?}", e),
+        // wait for a change, and then for the changes to settle
+        changed = wait_for_changes(&rx, &output_files, DEBOUNCE)?;
+    }
+}
+
+/// Waits for a change to the files sent by a watcher, and then for the changes to settle
+/// (until no change happens for `debounce`), returning the paths that changed.
+/// Reading files, and writing the `output_files`, are not changes.
+fn wait_for_changes(
+    rx: &Receiver<notify::Result<notify::Event>>,
+    output_files: &[PathBuf],
+    debounce: Duration,
+) -> Result<BTreeSet<PathBuf>> {
+    let mut changed = BTreeSet::new();
+    let mut dirty = false;
+    loop {
+        let event = if dirty {
+            match rx.recv_timeout(debounce) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => return Ok(changed),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(miette::miette!("the file watcher stopped"));
+                }
+            }
+        } else {
+            rx.recv()
+                .into_diagnostic()
+                .wrap_err("the file watcher stopped")?
+        };
+
+        match event {
+            // reading files (which we do when building) is not a change
+            Ok(event) if matches!(event.kind, notify::EventKind::Access(_)) => (),
+            Ok(event) if only_touches(&event, output_files) => (),
+            Ok(event) => {
+                dirty = true;
+                changed.extend(event.paths);
+            }
+            Err(e) => eprintln!("{:?}", miette::Report::new(SpecError::WatchFailed(e))),
         }
     }
 }
+
+/// Lists the files that changed, relative to `root` (only naming the first few of them)
+fn summarize_changes(changed: &BTreeSet<PathBuf>, root: &Path) -> String {
+    const NAMED: usize = 3;
+
+    let mut names: Vec<_> = changed
+        .iter()
+        .take(NAMED)
+        .map(|path| relative_display(path, root))
+        .collect();
+    if changed.len() > NAMED {
+        names.push(format!("{} more", changed.len() - NAMED));
+    }
+
+    match names.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {last}", rest.join(", ")),
+        _ => names.concat(),
+    }
+}
+
+/// Returns true if the event only concerns the given files (or the files of the given directories)
+fn only_touches(event: &notify::Event, files: &[PathBuf]) -> bool {
+    let files: Vec<_> = files
+        .iter()
+        .filter_map(|file| fs::canonicalize(file).ok())
+        .collect();
+
+    !event.paths.is_empty()
+        && event.paths.iter().all(|path| {
+            fs::canonicalize(path).is_ok_and(|path| files.iter().any(|file| path.starts_with(file)))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::{event::ModifyKind, Event, EventKind};
+
+    fn modified(path: &Path) -> notify::Result<Event> {
+        Ok(Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path.to_path_buf()))
+    }
+
+    #[test]
+    fn test_summarize_changes() {
+        let root = Path::new("/spec");
+        let changed = |names: &[&str]| names.iter().map(|name| root.join(name)).collect();
+        assert_eq!(summarize_changes(&changed(&["a.rs"]), root), "a.rs");
+        assert_eq!(
+            summarize_changes(&changed(&["b.rs", "a.rs"]), root),
+            "a.rs and b.rs"
+        );
+        assert_eq!(
+            summarize_changes(
+                &changed(&["src/a.rs", "b.rs", "c.rs", "d.rs", "e.rs"]),
+                root
+            ),
+            "b.rs, c.rs, d.rs and 2 more"
+        );
+    }
+
+    #[test]
+    fn test_wait_for_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let (lib, output) = (dir.path().join("lib.rs"), dir.path().join("spec.md"));
+        fs::write(&output, "").unwrap();
+        let (tx, rx) = channel();
+
+        // the changes made in a row are reported together, ignoring reads and the output files
+        tx.send(Ok(Event::new(EventKind::Access(
+            notify::event::AccessKind::Any,
+        ))
+        .add_path(lib.clone())))
+            .unwrap();
+        tx.send(modified(&output)).unwrap();
+        tx.send(modified(&lib)).unwrap();
+        tx.send(modified(&lib)).unwrap();
+        tx.send(modified(&dir.path().join("template.md"))).unwrap();
+        let changes = wait_for_changes(&rx, &[output], Duration::from_millis(10)).unwrap();
+        assert_eq!(
+            changes,
+            BTreeSet::from([lib.clone(), dir.path().join("template.md")])
+        );
+
+        // changes keep coming in until they settle
+        let sender = std::thread::spawn(move || {
+            for _ in 0..5 {
+                tx.send(modified(&lib)).unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            tx
+        });
+        let changes = wait_for_changes(&rx, &[], Duration::from_secs(1)).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(rx.try_recv().is_err());
+
+        drop(sender.join().unwrap());
+        assert!(wait_for_changes(&rx, &[], Duration::from_millis(10)).is_err());
+    }
+
+    #[test]
+    fn test_invalidate_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("lib.rs");
+        let extract = |cache: &mut ExtractionCache| {
+            let blocks = cache.parse_file(&file, &HashMap::new()).unwrap();
+            comment_parser::join_blocks(&blocks)
+        };
+
+        let mut cache = ExtractionCache::default();
+        fs::write(&file, "//~ one\n").unwrap();
+        let modified = fs::metadata(&file).unwrap().modified().unwrap();
+        assert_eq!(extract(&mut cache), "one\n");
+
+        // a change keeping the same size within the same clock tick goes unnoticed
+        fs::write(&file, "//~ two\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(extract(&mut cache), "one\n");
+
+        cache.invalidate(&BTreeSet::from([dir.path().to_path_buf()]));
+        assert_eq!(extract(&mut cache), "two\n");
+    }
+}
@@ -1,37 +1,170 @@
-use std::{fmt::Write as FmtWrite, path::Path};
+use std::{collections::HashMap, fmt::Write as FmtWrite, path::Path};
 
 use miette::{IntoDiagnostic, NamedSource, Result, WrapErr};
 
-use crate::errors::SpecError;
+use crate::{
+    errors::SpecError,
+    rust_parser::{self, RustItem},
+    toml_parser::Language,
+};
 
 /// The prefix to any spec instructions
 const SPECIFICATION_INSTRUCTION: &str = "spec:";
 
-/// Parse a file and return the specification-related content
-pub fn parse_file(file_name: &Path) -> Result<String> {
+/// A way to write spec comments, as (start delimiter, end delimiter for block comments)
+pub type CommentSyntax<'a> = (&'a str, Option<&'a str>);
+
+/// The comment syntax of files that are not listed in [LANGUAGES]
+const DEFAULT_COMMENTS: &[CommentSyntax] =
+    &[("//~", None), ("/*~", Some("*/")), ("/**~", Some("*/"))];
+
+/// The extensions of the languages using the [DEFAULT_COMMENTS] syntax,
+/// picked up (along with the extensions of [LANGUAGES]) when extracting a whole directory
+const DEFAULT_EXTENSIONS: &[&str] = &[
+    "rs", "c", "h", "cc", "cpp", "cxx", "hh", "hpp", "cs", "java", "kt", "kts", "scala", "groovy",
+    "go", "js", "mjs", "cjs", "jsx", "ts", "tsx", "swift", "dart", "zig", "php", "sol", "move",
+    "cairo", "proto",
+];
+
+/// Whether a file has an extension whose spec comments we know how to extract:
+/// markdown, the extensions with a built-in syntax, and the ones listed in `languages`
+pub fn is_supported(file_name: &Path, languages: &HashMap<String, Language>) -> bool {
+    let Some(extension) = file_name
+        .extension()
+        .and_then(|extension| extension.to_str())
+    else {
+        return false;
+    };
+
+    extension == "md"
+        || languages.contains_key(extension)
+        || DEFAULT_EXTENSIONS.contains(&extension)
+        || LANGUAGES.iter().any(|(ext, ..)| *ext == extension)
+}
+
+/// A contiguous run of specification comments extracted from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecBlock {
+    /// The line (starting at 1) where the block starts in the source file
+    pub line: usize,
+    /// The extracted specification text
+    pub content: String,
+    /// The names of the `spec:begin(name)` regions the block is in
+    pub anchors: Vec<String>,
+}
+
+/// Concatenates the content of extracted blocks
+pub fn join_blocks(blocks: &[SpecBlock]) -> String {
+    blocks.iter().map(|block| block.content.as_str()).collect()
+}
+
+/// Parse a file and return the specification-related content,
+/// using the comment syntax of `languages` if its extension is listed there
+pub fn parse_file(
+    file_name: &Path,
+    languages: &HashMap<String, Language>,
+) -> Result<Vec<SpecBlock>> {
     //~ parsing is based on the extension of the file:
-    match Path::new(file_name)
+    let extension = Path::new(file_name)
         .extension()
         .ok_or_else(|| SpecError::CantParseFile(file_name.to_path_buf()))?
         .to_str()
-        .expect("couldn't convert the extension to a string")
-    {
+        .expect("couldn't convert the extension to a string");
+
+    //~ - extensions listed in the `[languages]` table of the manifest use the
+    //~   `start` and `end` delimiters (and `code_fence` language) given there
+    if let Some(language) = languages.get(extension) {
+        let lang = language.code_fence.as_deref().unwrap_or(extension);
+        let comments = [(language.start.as_str(), language.end.as_deref())];
+        return parse_code(lang, &comments, file_name);
+    }
+
+    match extension {
         //~ - for markdown files, we retrieve the entire content
         "md" => std::fs::read_to_string(file_name)
             .into_diagnostic()
-            .wrap_err_with(|| format!("could not read file {}", file_name.display())),
-
-        //~ - for python files we look for comments starting with `#~`
-        "py" => parse_code("python", "#~", None, file_name),
-
-        //~ - for ML files we look for comments starting with `#~`
-        "ml" | "mli" => parse_code("ocaml", "(*~", Some("*)"), file_name),
+            .wrap_err_with(|| format!("could not read file {}", file_name.display()))
+            .map(|content| {
+                vec![SpecBlock {
+                    line: 1,
+                    content,
+                    anchors: vec![],
+                }]
+            }),
 
-        //~ - for other files we look for comments starting with `//~`
-        ext => parse_code(ext, "//~", None, file_name),
+        //~ - for extensions listed in the built-in table below,
+        //~   we look for comments using the delimiters of the language
+        //~   (an extension can be listed several times, for line and block comments)
+        //~ - for other files we look for comments starting with `//~`,
+        //~   or block comments between `/*~` (or `/**~`) and `*/`
+        ext => {
+            let mut syntaxes = LANGUAGES.iter().filter(|(extension, ..)| *extension == ext);
+            match syntaxes.next() {
+                Some((_, lang, start, end)) => {
+                    let mut comments = vec![(*start, *end)];
+                    comments.extend(syntaxes.map(|(_, _, start, end)| (*start, *end)));
+                    parse_code(lang, &comments, file_name)
+                }
+                None => parse_code(ext, DEFAULT_COMMENTS, file_name),
+            }
+        }
     }
 }
 
+//~
+//~ the built-in languages are the following:
+//~
+//~ spec:startcode
+/// The built-in comment syntax of file extensions, as
+/// (extension, code fence language, start delimiter, end delimiter for block comments)
+const LANGUAGES: &[(&str, &str, &str, Option<&str>)] = &[
+    // `#` comments
+    ("py", "python", "#~", None),
+    ("sh", "sh", "#~", None),
+    ("bash", "bash", "#~", None),
+    ("zsh", "zsh", "#~", None),
+    ("toml", "toml", "#~", None),
+    ("yaml", "yaml", "#~", None),
+    ("yml", "yaml", "#~", None),
+    ("rb", "ruby", "#~", None),
+    ("pl", "perl", "#~", None),
+    ("r", "r", "#~", None),
+    ("R", "r", "#~", None),
+    ("jl", "julia", "#~", None),
+    ("nix", "nix", "#~", None),
+    ("ex", "elixir", "#~", None),
+    ("exs", "elixir", "#~", None),
+    // `--` comments
+    ("hs", "haskell", "--~", None),
+    ("hs", "haskell", "{-~", Some("-}")),
+    ("elm", "elm", "--~", None),
+    ("elm", "elm", "{-~", Some("-}")),
+    ("sql", "sql", "--~", None),
+    ("sql", "sql", "/*~", Some("*/")),
+    ("lua", "lua", "--~", None),
+    ("lua", "lua", "--[[~", Some("]]")),
+    // `;` comments
+    ("lisp", "lisp", ";~", None),
+    ("el", "elisp", ";~", None),
+    ("scm", "scheme", ";~", None),
+    ("rkt", "racket", ";~", None),
+    ("clj", "clojure", ";~", None),
+    ("cljs", "clojure", ";~", None),
+    // `%` comments
+    ("erl", "erlang", "%~", None),
+    ("hrl", "erlang", "%~", None),
+    ("tex", "latex", "%~", None),
+    ("sty", "latex", "%~", None),
+    // block comments
+    ("ml", "ocaml", "(*~", Some("*)")),
+    ("mli", "ocaml", "(*~", Some("*)")),
+    ("html", "html", "<!--~", Some("-->")),
+    ("htm", "html", "<!--~", Some("-->")),
+    ("xml", "xml", "<!--~", Some("-->")),
+    ("svg", "xml", "<!--~", Some("-->")),
+];
+//~ spec:endcode
+
 /// detects if a comment ends on this same line
 fn has_end(end: &str, comment: &str) -> bool {
     comment.trim().ends_with(end)
@@ -42,21 +175,31 @@ fn has_end(end: &str, comment: &str) -> bool {
 //~
 
 /// Parse code to return the specification-related content
-/// (comments that start with a special delimiter, by default `~`)
+/// (comments that start with a special delimiter, by default `~`),
+/// where spec comments can be written using any of the given syntaxes
 pub fn parse_code(
     lang: &str,
-    start_comment: &str,
-    end_comment: Option<&str>,
+    comments: &[CommentSyntax],
     file_name: &Path,
-) -> Result<String> {
+) -> Result<Vec<SpecBlock>> {
     // set to the offset of the startcode if we're waiting for an endcode instruction
     let mut extract_code = None;
 
-    // set to the indentation of the 1st line if we're within a multi-line in a comment
-    let mut in_spec_comment = None;
+    // set to the indentation of the 1st line (and the end delimiter)
+    // if we're within a multi-line in a comment
+    let mut in_spec_comment: Option<(usize, &str)> = None;
 
     // to store the result of extracting doc comments
-    let mut result = String::new();
+    let mut blocks = vec![];
+
+    // the block currently being extracted
+    let mut block: Option<SpecBlock> = None;
+
+    // the names (and offsets) of the `spec:begin(name)` regions we're in
+    let mut anchors: Vec<(String, usize)> = vec![];
+
+    // the items of the file, parsed the first time a `spec:item` instruction is seen
+    let mut rust_items: Option<Vec<RustItem>> = None;
 
     // read file
     let source = std::fs::read_to_string(file_name)
@@ -65,21 +208,37 @@ pub fn parse_code(
 
     // go over the file line by line
     let mut byte_offset_for_errors = 0;
-    for line in source.lines() {
+    for (line_number, line) in source.lines().enumerate() {
         //~ 1. only print a normal line if it is between `//~ spec:startcode` and `//~spec:endcode` statements
-        if !line.trim_start().starts_with(start_comment) && in_spec_comment.is_none() {
+        //~    (any other line ends the current block of spec comments)
+        let syntax = comments
+            .iter()
+            .find(|(start, _)| line.trim_start().starts_with(start));
+        if syntax.is_none() && in_spec_comment.is_none() {
             if extract_code.is_some() {
                 // TODO: reset indentation
-                writeln!(&mut result, "{}", line).unwrap();
+                let result = &mut block.as_mut().expect("startcode opens a block").content;
+                writeln!(result, "{}", line).unwrap();
+            } else if let Some(block) = block.take() {
+                blocks.push(block);
             }
 
             byte_offset_for_errors += line.len() + 1; // +1 for the newline character
             continue;
         }
 
+        // any spec comment belongs to a block, which we record the line of
+        let result = &mut block
+            .get_or_insert_with(|| SpecBlock {
+                line: line_number + 1,
+                content: String::new(),
+                anchors: anchors.iter().map(|(name, _)| name.clone()).collect(),
+            })
+            .content;
+
         //~ 2. if we are within a multi-line comment, we remove the indentation
         //~   based on the indentation of the first line of the comment
-        let comment = if let Some(indentation) = in_spec_comment {
+        let comment = if let Some((indentation, _)) = in_spec_comment {
             let left_trimmed = line.trim_start();
             let whitespaces_len = line.len() - left_trimmed.len();
             if indentation > whitespaces_len {
@@ -90,6 +249,7 @@ pub fn parse_code(
         } else {
             //~ 3. otherwise, we extract what comes after the comment delimiter
             //~   (note that the result might still have a starting space)
+            let (start_comment, _) = syntax.unwrap();
             line.split_once(start_comment).unwrap().1
         };
 
@@ -105,34 +265,108 @@ pub fn parse_code(
                 .next()
                 .unwrap();
 
-            match instruction {
+            // some instructions take an argument, as in `spec:begin(name)`
+            let (instruction, argument) = match instruction.split_once('(') {
+                Some((instruction, rest)) => (instruction, rest.strip_suffix(')').or(Some(""))),
+                None => (instruction, None),
+            };
+
+            match (instruction, argument) {
                 //~~ - a comment starting with `//~ spec:startcode` will print
                 //~       every line afterwards, up until a `//~ spec:endcode` statement
-                "startcode" if extract_code.is_none() => {
+                ("startcode", None) if extract_code.is_none() => {
                     let column = line.find("startcode").unwrap();
-                    writeln!(&mut result, "```{lang}").unwrap();
+                    writeln!(result, "```{lang}").unwrap();
                     extract_code = Some(byte_offset_for_errors + column);
                 }
-                "startcode" if extract_code.is_some() => {
+                ("startcode", None) if extract_code.is_some() => {
                     let column = line.find("startcode").unwrap();
                     return Err(SpecError::DoubleStartcode {
                         _src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
                         _bad_bit: (byte_offset_for_errors + column, "startcode".len()),
-                    })
-                    .into_diagnostic();
+                    }
+                    .into());
                 }
                 // spec:endcode ends spec:startcode
-                "endcode" if extract_code.is_some() => {
-                    writeln!(&mut result, "```").unwrap();
+                ("endcode", None) if extract_code.is_some() => {
+                    writeln!(result, "```").unwrap();
                     extract_code = None;
                 }
-                "endcode" if extract_code.is_none() => {
+                ("endcode", None) if extract_code.is_none() => {
                     let column = line.find("endcode").unwrap();
                     return Err(SpecError::MissingStartcode {
                         _src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
                         _bad_bit: (byte_offset_for_errors + column, "endcode".len()),
-                    })
-                    .into_diagnostic();
+                    }
+                    .into());
+                }
+                //~~ - in Rust files, a comment starting with `//~ spec:item` will print
+                //~       the next item (struct, enum, trait, the signature of a function, etc.)
+                ("item", None) if extract_code.is_none() => {
+                    let column = line.find("item").unwrap();
+                    let bad_bit = (byte_offset_for_errors + column, "item".len());
+
+                    if rust_items.is_none() {
+                        if file_name.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+                            return Err(SpecError::ItemNotInRust {
+                                _src: NamedSource::new(
+                                    file_name.to_string_lossy(),
+                                    source.to_string(),
+                                ),
+                                _bad_bit: bad_bit,
+                            }
+                            .into());
+                        }
+
+                        let items = rust_parser::parse_items(&source).map_err(|e| {
+                            SpecError::CantParseRust {
+                                _src: NamedSource::new(
+                                    file_name.to_string_lossy(),
+                                    source.to_string(),
+                                ),
+                                _bad_bit: (rust_parser::offset(&source, e.span().start()), 0),
+                            }
+                        });
+                        rust_items = Some(items?);
+                    }
+
+                    let items = rust_items.as_deref().unwrap_or_default();
+                    let Some(item) = rust_parser::next_item(items, line_number + 1) else {
+                        return Err(SpecError::MissingItem {
+                            _src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            _bad_bit: bad_bit,
+                        }
+                        .into());
+                    };
+                    writeln!(result, "```{lang}\n{}\n```", item.source).unwrap();
+                }
+                //~~ - a comment starting with `//~ spec:begin(name)` starts a region named `name`,
+                //~       up until a `//~ spec:end(name)` statement,
+                //~       which can be extracted on its own using `file#name` in the manifest
+                ("begin" | "end", Some(name)) if extract_code.is_some() || name.is_empty() => {
+                    let column = line.find(instruction).unwrap();
+                    return Err(SpecError::BadAnchor {
+                        _src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                        _bad_bit: (byte_offset_for_errors + column, instruction.len()),
+                    }
+                    .into());
+                }
+                ("begin", Some(name)) => {
+                    blocks.extend(block.take().filter(|block| !block.content.is_empty()));
+                    let column = line.find("begin").unwrap();
+                    anchors.push((name.to_string(), byte_offset_for_errors + column));
+                }
+                ("end", Some(name)) => {
+                    blocks.extend(block.take().filter(|block| !block.content.is_empty()));
+                    let Some(position) = anchors.iter().position(|(open, _)| open == name) else {
+                        let column = line.find("end").unwrap();
+                        return Err(SpecError::MissingAnchorBegin {
+                            _src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            _bad_bit: (byte_offset_for_errors + column, "end".len()),
+                        }
+                        .into());
+                    };
+                    anchors.remove(position);
                 }
                 //~~ - error on any other instructions
                 _ => {
@@ -147,18 +381,24 @@ pub fn parse_code(
             };
         } else {
             //~ 5. if we are not seeing an instruction, figure out if:
+            let continued = in_spec_comment.is_some();
+            let end_comment = match in_spec_comment {
+                Some((_, end)) => Some(end),
+                None => syntax.and_then(|(_, end)| *end),
+            };
             let comment = if let Some(end) = end_comment {
                 if has_end(end, comment) {
                     //~~ - the comment is ending
 
                     in_spec_comment = None;
-                    comment.trim_end_matches(end)
+                    comment.trim_end().trim_end_matches(end).trim_end()
                 } else {
                     //~~ - or goes on to the next line
 
                     if in_spec_comment.is_none() {
+                        let (start_comment, _) = syntax.unwrap();
                         let offset = line.find(start_comment).unwrap() + start_comment.len();
-                        in_spec_comment = Some(offset);
+                        in_spec_comment = Some((offset, end));
                     }
 
                     comment
@@ -167,6 +407,15 @@ pub fn parse_code(
                 comment
             };
 
+            //~    (in comments ending with `*/`, the `*` starting the next lines is removed,
+            //~    so that `/**~` comments can be written with a column of stars)
+            let comment = match end_comment {
+                Some("*/") if continued => {
+                    comment.trim_start().strip_prefix('*').unwrap_or(comment)
+                }
+                _ => comment,
+            };
+
             //~ 6. Finally, extract the specification text.
             //~    Each `~` at the start of the comment,
             //~    not including the first one,
@@ -183,7 +432,7 @@ pub fn parse_code(
             let comment = no_more_tilde.strip_prefix(' ').unwrap_or(comment);
             indented.push_str(comment);
 
-            writeln!(&mut result, "{indented}").unwrap();
+            writeln!(result, "{indented}").unwrap();
         }
 
         byte_offset_for_errors += line.len() + 1; // +1 for the newline character
@@ -195,10 +444,20 @@ pub fn parse_code(
         return Err(SpecError::MissingEndcode {
             _src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
             _bad_bit: (offset, 0),
-        })
-        .into_diagnostic();
+        }
+        .into());
+    }
+
+    //~    and every begin instruction with an end instruction
+    if let Some((_, offset)) = anchors.first() {
+        return Err(SpecError::MissingAnchorEnd {
+            _src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+            _bad_bit: (*offset, "begin".len()),
+        }
+        .into());
     }
 
     //~ 8. return the result
-    Ok(result)
+    blocks.extend(block);
+    Ok(blocks)
 }
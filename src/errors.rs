@@ -53,10 +53,404 @@ pub enum SpecError {
         #[source_code]
         _src: NamedSource<String>,
 
-        #[label("try spec:startcode or spec:endcode instead")]
+        #[label("try spec:startcode, spec:endcode, spec:item, spec:begin(name) or spec:end(name) instead")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "spec:item must be followed by an item (struct, enum, function, trait, etc.)"
+    ))]
+    MissingItem {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("there are no items after this instruction")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("spec:item only works in Rust files"))]
+    ItemNotInRust {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("try spec:startcode and spec:endcode instead")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("spec:item needs the file to be valid Rust"))]
+    CantParseRust {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this is not valid Rust")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("embed code with {{code \"path/to/file.rs\" \"ItemName\"}}"))]
+    BadCodeDirective {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("malformed code directive")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("code can only be embedded from Rust files"))]
+    CodeNotInRust {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this is not a Rust file")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help(
+        "the item must be defined in the file, possibly within a module, an impl block or a trait"
+    ))]
+    MissingCodeItem {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("no item with this name")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("pick one with its path within the file: {candidates}"))]
+    AmbiguousCodeItem {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("several items have this name")]
+        _bad_bit: (usize, usize),
+
+        candidates: String,
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("define the section in the [sections] table of the specification file"))]
+    MissingSection {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("section `{name}` is not defined")]
+        _bad_bit: (usize, usize),
+
+        name: String,
+    },
+
+    #[error("Section `{name}` is not used by the template")]
+    #[diagnostic(
+        severity(Warning),
+        help("use it in the template with {{sections.{name}}}, or remove it")
+    )]
+    UnusedSection {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this section is never used")]
+        _bad_bit: (usize, usize),
+
+        name: String,
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("include a template with {{include \"path/to/file.md\"}}"))]
+    BadIncludeDirective {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("malformed include directive")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("included templates are relative to the template including them"))]
+    MissingInclude {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this template can't be found")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("templates can't include themselves: {cycle}"))]
+    IncludeCycle {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this include creates a cycle")]
+        _bad_bit: (usize, usize),
+
+        cycle: String,
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help(
+        "available filters: shift_headings [N], strip_heading, admonition KIND [\"title\"], number_list, indent [N] and details [\"summary\"]"
+    ))]
+    BadFilter {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("unknown filter, or invalid arguments")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing template")]
+    #[diagnostic(help("{reason}"))]
+    BadTemplate {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("here")]
+        _bad_bit: (usize, usize),
+
+        reason: String,
+    },
+
+    #[error("Error rendering template")]
+    #[diagnostic(help("{reason}"))]
+    CantRenderTemplate {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("here")]
+        _bad_bit: (usize, usize),
+
+        reason: String,
+    },
+
+    #[error("Broken link")]
+    #[diagnostic(
+        severity(Warning),
+        help("relative links are resolved from the directory of the specification file")
+    )]
+    BrokenLink {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this file doesn't exist")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Broken link")]
+    #[diagnostic(
+        severity(Warning),
+        help("link to a heading of the specification, like [foo](#some-heading)")
+    )]
+    MissingLinkAnchor {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("no such anchor in the rendered specification")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Heading level jumps from {from} to {to}")]
+    #[diagnostic(
+        severity(Warning),
+        help("headings should only go one level deeper at a time")
+    )]
+    HeadingJump {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this heading skips a level")]
+        _bad_bit: (usize, usize),
+
+        from: usize,
+        to: usize,
+    },
+
+    #[error("Unbalanced code fence")]
+    #[diagnostic(severity(Warning), help("close the code block with the same fence"))]
+    UnbalancedFence {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this code block is never closed")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Trailing whitespace")]
+    #[diagnostic(
+        severity(Warning),
+        help("remove the whitespace at the end of the line")
+    )]
+    TrailingWhitespace {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("here")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Unmatched `$`")]
+    #[diagnostic(
+        severity(Warning),
+        help("close the math with another `$`, or escape it as `\\$`")
+    )]
+    UnmatchedDollar {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this math is never closed")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("missing end instruction"))]
+    MissingAnchorEnd {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label(
+            "this begin instruction is not terminated by an end instruction with the same name"
+        )]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("missing a begin instruction with the same name before the end"))]
+    MissingAnchorBegin {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("This bit here")]
+        _bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "begin and end instructions need a name and can't be within startcode and endcode"
+    ))]
+    BadAnchor {
+        #[source_code]
+        _src: NamedSource<String>,
+
+        #[label("this instruction is invalid")]
         _bad_bit: (usize, usize),
     },
 
     #[error("This is not a git repository, you can't use `@/` in the path of section {0}")]
     NotGitRepo(String),
+
+    #[error("Invalid glob pattern `{0}`")]
+    #[diagnostic(help("see https://docs.rs/glob for the supported syntax"))]
+    BadGlob(String),
+
+    #[error("No files match `{1}` in section {0}")]
+    #[diagnostic(help(
+        "a section can be a file, a directory, or a glob pattern like `src/**/*.rs`"
+    ))]
+    NoMatchingFiles(String, String),
+
+    #[error("Found {0} problems in the spec comments")]
+    LintProblems(usize),
+
+    #[error("The specification file `{0}` is not up to date")]
+    #[diagnostic(help("run `cargo spec build` to rebuild it"))]
+    OutdatedSpec(PathBuf),
+
+    #[error("Could not write the specification to `{0}`")]
+    #[diagnostic(help(
+        "the directory `{1}` doesn't exist, create it or pick another output file"
+    ))]
+    MissingOutputDir(PathBuf, String),
+
+    #[error("Could not write the specification to `{0}`")]
+    #[diagnostic(help("you don't have the permission to write there, pick another output file"))]
+    OutputPermissionDenied(PathBuf),
+
+    #[error("Could not write the specification to `{0}`")]
+    CantWriteOutput(PathBuf, #[source] std::io::Error),
+
+    #[error("Could not write the specification")]
+    CantWriteStream(#[source] std::io::Error),
+
+    #[error("The book format is a directory, it can't be written to a stream")]
+    #[diagnostic(help("write it to a directory with --output-file"))]
+    DirectoryToStream,
+
+    #[error("`cargo spec {0}` can't use stdout as its output file")]
+    #[diagnostic(help(
+        "pass the file the specification is written to with --output-file, or use `cargo spec build -o -`"
+    ))]
+    StdoutOutputFile(&'static str),
+
+    #[error("The PDF fonts can't render these characters: {0}")]
+    #[diagnostic(help(
+        "the pdf format only supports Latin-1 and a few typographic characters, use the html or latex format instead"
+    ))]
+    UnsupportedPdfCharacters(String),
+
+    #[error("Could not watch `{0}`")]
+    #[diagnostic(help(
+        "make sure it exists, and that the system allows watching that many files (see `fs.inotify.max_user_watches` on Linux)"
+    ))]
+    CantWatch(PathBuf, #[source] notify::Error),
+
+    #[error("Could not serve the specification on port {0}")]
+    #[diagnostic(help("the port might already be in use, pick another one with --port"))]
+    CantServe(u16, #[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Error while watching the specification files")]
+    WatchFailed(#[source] notify::Error),
+
+    #[error("Can't write several specifications to `{0}`")]
+    #[diagnostic(help(
+        "set the `output_file` of each `[[spec]]` in the specification file instead"
+    ))]
+    SeveralSpecsOutputFile(PathBuf),
+
+    #[error("`{0}` lists several specifications")]
+    #[diagnostic(help(
+        "build each `[[spec]]` with `SpecBuilder::new`, after parsing the file with `toml_parser::parse_manifest`"
+    ))]
+    SeveralSpecs(PathBuf),
+
+    #[error("Several specifications are written to `{0}`")]
+    #[diagnostic(help("give each `[[spec]]` its own `output_file`"))]
+    DuplicateOutputFile(PathBuf),
+
+    #[error("Several specifications are served at `{0}`")]
+    #[diagnostic(help("give the `output_file` of each `[[spec]]` a different path"))]
+    DuplicatePage(String),
+
+    #[error("No cargo workspace contains `{0}`")]
+    #[diagnostic(help("--workspace looks for a Cargo.toml with a [workspace] table"))]
+    NoWorkspace(PathBuf),
+
+    #[error("No member of the workspace at `{0}` has a Specification.toml")]
+    #[diagnostic(help("run `cargo spec init <CRATE_DIR>` to start a specification in a crate"))]
+    NoWorkspaceSpecs(PathBuf),
+
+    #[error("The specifications of these crates failed: {}", .0.join(", "))]
+    FailedMembers(Vec<String>),
+
+    #[error("No block named `{1}` in section {0}")]
+    #[diagnostic(help("name a block with `spec:begin({1})` and `spec:end({1})` instructions"))]
+    MissingAnchor(String, String),
+
+    #[error("Could not find the current git commit to link to the source")]
+    #[diagnostic(help("`source_links` only works in a git repository with at least one commit"))]
+    NoGitCommit,
+
+    #[error("The `origin` remote of this repository is not hosted on {0}")]
+    #[diagnostic(help("use a URL pattern with `{{commit}}`, `{{path}}` and `{{line}}` instead"))]
+    NoGitRemote(String),
+
+    #[error("`{0}` is not a valid `source_links` value")]
+    #[diagnostic(help(
+        "use `github`, `gitlab`, or a URL pattern containing `{{commit}}` and `{{path}}` (and optionally `{{line}}`)"
+    ))]
+    BadSourceLinks(String),
 }
@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+
+use miette::Result;
+use tinytemplate::TinyTemplate;
+
+use crate::{
+    errors::SpecError,
+    markdown::{fence, heading_level},
+    template::IncludedTemplates,
+};
+
+/// The formatters that tinytemplate already provides
+const BUILTIN_FORMATTERS: &[&str] = &["unescaped"];
+
+/// A filter transforming a value at render time, as in `{sections.foo | shift_headings 1}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    /// `shift_headings [N]`: makes the headings N levels deeper (shallower if N is negative)
+    ShiftHeadings(i32),
+    /// `strip_heading`: removes the first heading
+    StripHeading,
+    /// `admonition KIND ["title"]`: wraps the content in an mdBook admonition
+    Admonition(String, Option<String>),
+    /// `number_list`: turns the top-level bullet lists into numbered lists
+    NumberList,
+    /// `indent [N]`: indents every line by N spaces
+    Indent(usize),
+    /// `details ["summary"]`: collapses the content into a `<details>` element
+    Details(String),
+}
+
+/// Registers a formatter for every chain of filters used in the template
+/// (as in `{sections.foo | shift_headings 1 | details "Foo"}`),
+/// since tinytemplate formatters don't take arguments,
+/// where `template` has the same offsets as the `included` templates it's made of
+pub fn register<'t>(
+    tt: &mut TinyTemplate<'t>,
+    template: &'t str,
+    included: &IncludedTemplates,
+) -> Result<()> {
+    let mut registered = HashSet::new();
+    for name in formatter_names(template) {
+        if BUILTIN_FORMATTERS.contains(&name) || !registered.insert(name) {
+            continue;
+        }
+
+        let filters = parse_chain(name).map_err(|(start, len)| {
+            let (_src, _bad_bit) = included.source((offset(template, name) + start, len));
+            SpecError::BadFilter { _src, _bad_bit }
+        })?;
+
+        tt.add_formatter(name, move |value, output| {
+            let mut content = String::new();
+            tinytemplate::format_unescaped(value, &mut content)?;
+            output.push_str(&apply(&filters, content));
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+/// Lists the formatter names of the value tags of a template,
+/// as tinytemplate parses them (everything after the first `|`)
+fn formatter_names(template: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + len];
+        rest = &rest[start + len + 1..];
+
+        // blocks (`{{ if x }}`) and comments (`{# comment #}`) have no formatters
+        if tag.starts_with(['{', '#']) {
+            continue;
+        }
+
+        let tag = tag.trim().trim_end_matches('-').trim();
+        if let Some((_, name)) = tag.split_once('|') {
+            names.push(name.trim());
+        }
+    }
+    names
+}
+
+/// The offset of a slice of `s` within it
+fn offset(s: &str, slice: &str) -> usize {
+    slice.as_ptr() as usize - s.as_ptr() as usize
+}
+
+/// Parses filters separated by `|`, returning the span of the invalid one on error
+fn parse_chain(chain: &str) -> Result<Vec<Filter>, (usize, usize)> {
+    split_outside_quotes(chain)
+        .into_iter()
+        .map(|segment| {
+            let segment = segment.trim();
+            tokenize(segment)
+                .and_then(|tokens| parse_filter(&tokens))
+                .ok_or((offset(chain, segment), segment.len()))
+        })
+        .collect()
+}
+
+/// Splits a chain of filters at the `|` that are not within quotes
+fn split_outside_quotes(chain: &str) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in chain.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '|' if !in_quotes => {
+                segments.push(&chain[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    segments.push(&chain[start..]);
+    segments
+}
+
+/// Splits a filter into words, where a quoted string is a single word
+fn tokenize(filter: &str) -> Option<Vec<String>> {
+    let mut tokens = vec![];
+    let mut rest = filter.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            tokens.push(quoted[..end].to_string());
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push(rest[..end].to_string());
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Some(tokens)
+}
+
+fn parse_filter(tokens: &[String]) -> Option<Filter> {
+    let (name, args) = tokens.split_first()?;
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    let filter = match (name.as_str(), args.as_slice()) {
+        ("shift_headings", []) => Filter::ShiftHeadings(1),
+        ("shift_headings", [n]) => Filter::ShiftHeadings(n.parse().ok()?),
+        ("strip_heading", []) => Filter::StripHeading,
+        ("admonition", [kind]) => Filter::Admonition(kind.to_string(), None),
+        ("admonition", [kind, title]) => {
+            Filter::Admonition(kind.to_string(), Some(title.to_string()))
+        }
+        ("number_list", []) => Filter::NumberList,
+        ("indent", []) => Filter::Indent(4),
+        ("indent", [n]) => Filter::Indent(n.parse().ok()?),
+        ("details", []) => Filter::Details("Details".to_string()),
+        ("details", [summary]) => Filter::Details(summary.to_string()),
+        _ => return None,
+    };
+    Some(filter)
+}
+
+/// Applies filters, in order, to some markdown content
+fn apply(filters: &[Filter], mut content: String) -> String {
+    for filter in filters {
+        content = match filter {
+            Filter::ShiftHeadings(n) => map_headings(&content, |level, line| {
+                let shifted = (level as i32 + n).clamp(1, 6) as usize;
+                format!("{}{}", "#".repeat(shifted), &line[level..])
+            }),
+            Filter::StripHeading => strip_heading(&content),
+            Filter::Admonition(kind, title) => {
+                let title = title
+                    .as_ref()
+                    .map(|title| format!(" \"{title}\""))
+                    .unwrap_or_default();
+                // the fence is longer than the code blocks of the content,
+                // so that they don't close the admonition
+                let fence = "`".repeat(longest_backtick_run(&content).max(2) + 1);
+                format!(
+                    "{fence}admonish {kind}{title}\n{}{fence}\n",
+                    with_newline(&content)
+                )
+            }
+            Filter::NumberList => number_list(&content),
+            Filter::Indent(n) => content
+                .split_inclusive('\n')
+                .map(|line| {
+                    if line.trim().is_empty() {
+                        line.to_string()
+                    } else {
+                        format!("{}{line}", " ".repeat(*n))
+                    }
+                })
+                .collect(),
+            Filter::Details(summary) => format!(
+                "<details>\n<summary>{summary}</summary>\n\n{}\n</details>\n",
+                with_newline(&content)
+            ),
+        };
+    }
+    content
+}
+
+/// Ends content with a newline, unless it's empty
+fn with_newline(content: &str) -> String {
+    if content.is_empty() || content.ends_with('\n') {
+        content.to_string()
+    } else {
+        format!("{content}\n")
+    }
+}
+
+/// The length of the longest run of backticks in some content
+fn longest_backtick_run(content: &str) -> usize {
+    content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Replaces the heading lines outside of code blocks with the result of `f`,
+/// given the level of the heading and the line
+fn map_headings(content: &str, f: impl Fn(usize, &str) -> String) -> String {
+    let mut result = String::new();
+    let mut in_fence = None;
+    for line in content.split_inclusive('\n') {
+        let was_in_fence = in_fence.is_some();
+        in_fence = fence(line, in_fence);
+        match heading_level(line) {
+            Some(level) if !was_in_fence && in_fence.is_none() => result.push_str(&f(level, line)),
+            _ => result.push_str(line),
+        }
+    }
+    result
+}
+
+/// Removes the first heading, along with the blank lines following it
+fn strip_heading(content: &str) -> String {
+    let mut result = String::new();
+    let mut in_fence = None;
+    let mut lines = content.split_inclusive('\n');
+    for line in lines.by_ref() {
+        let was_in_fence = in_fence.is_some();
+        in_fence = fence(line, in_fence);
+        if !was_in_fence && in_fence.is_none() && heading_level(line).is_some() {
+            break;
+        }
+        result.push_str(line);
+    }
+
+    result.extend(lines.skip_while(|line| line.trim().is_empty()));
+    result
+}
+
+/// Numbers the items of the top-level bullet lists, restarting at every new list
+fn number_list(content: &str) -> String {
+    let mut result = String::new();
+    let mut in_fence = None;
+    let mut n = 1;
+    for line in content.split_inclusive('\n') {
+        let was_in_fence = in_fence.is_some();
+        in_fence = fence(line, in_fence);
+        if was_in_fence || in_fence.is_some() {
+            result.push_str(line);
+            continue;
+        }
+
+        match line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .or_else(|| line.strip_prefix("+ "))
+        {
+            Some(item) => {
+                result.push_str(&format!("{n}. {item}"));
+                n += 1;
+            }
+            None => {
+                // anything but a blank line or the continuation of an item ends the list
+                if !line.trim().is_empty() && !line.starts_with([' ', '\t']) {
+                    n = 1;
+                }
+                result.push_str(line);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(chain: &str, content: &str) -> String {
+        apply(&parse_chain(chain).unwrap(), content.to_string())
+    }
+
+    #[test]
+    fn test_parse_chain() {
+        assert_eq!(
+            parse_chain(r#"shift_headings 2 | details "A | B""#),
+            Ok(vec![
+                Filter::ShiftHeadings(2),
+                Filter::Details("A | B".to_string())
+            ])
+        );
+        assert_eq!(parse_chain("indent | nope"), Err((9, 4)));
+        assert_eq!(parse_chain("indent x"), Err((0, 8)));
+    }
+
+    #[test]
+    fn test_formatter_names() {
+        let template = "{{ if x }}{sections.a | indent 2}{{ endif }}{sections.b}{ c | unescaped -}";
+        assert_eq!(formatter_names(template), vec!["indent 2", "unescaped"]);
+    }
+
+    #[test]
+    fn test_headings() {
+        let content = "# Title\n\ntext\n\n```\n# not a heading\n```\n\n## Sub\n";
+        assert_eq!(
+            filter("shift_headings 2", content),
+            "### Title\n\ntext\n\n```\n# not a heading\n```\n\n#### Sub\n"
+        );
+        assert_eq!(filter("shift_headings -3", "## Sub\n"), "# Sub\n");
+        assert_eq!(
+            filter("strip_heading", content),
+            "text\n\n```\n# not a heading\n```\n\n## Sub\n"
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            filter(r#"admonition warning "Careful""#, "text"),
+            "```admonish warning \"Careful\"\ntext\n```\n"
+        );
+        assert_eq!(
+            filter("admonition note", "text\n\n```rust\nlet x = 1;\n```\n"),
+            "````admonish note\ntext\n\n```rust\nlet x = 1;\n```\n````\n"
+        );
+        assert_eq!(
+            filter("admonition note", "````md\n```\n````\n"),
+            "`````admonish note\n````md\n```\n````\n`````\n"
+        );
+        assert_eq!(
+            filter("details", "text\n"),
+            "<details>\n<summary>Details</summary>\n\ntext\n\n</details>\n"
+        );
+        assert_eq!(filter("indent 2", "a\n\n- b\n"), "  a\n\n  - b\n");
+    }
+
+    #[test]
+    fn test_number_list() {
+        assert_eq!(
+            filter("number_list", "- a\n  more\n\n* b\n\ntext\n\n- c\n"),
+            "1. a\n  more\n\n2. b\n\ntext\n\n1. c\n"
+        );
+    }
+}
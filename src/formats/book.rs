@@ -0,0 +1,255 @@
+use crate::{
+    markdown::{fence, heading_level, slugify},
+    toml_parser::Specification,
+};
+use serde::Serialize;
+use std::{collections::HashSet, path::PathBuf};
+
+/// A part of the specification, starting at a top-level heading
+#[derive(Debug, PartialEq, Eq)]
+pub struct Chapter {
+    /// The text of the heading
+    pub title: String,
+    /// A name usable in paths and URLs, derived from the title
+    pub slug: String,
+    /// The content of the chapter, its heading being the only level-1 heading
+    pub content: String,
+}
+
+/// Slugs that chapters can't use, as they would clash with the other generated files
+/// (on case-insensitive file systems too)
+const RESERVED_SLUGS: &[&str] = &["readme", "summary", "introduction"];
+
+/// The `book.toml` file of an mdBook project
+#[derive(Serialize)]
+struct BookToml<'a> {
+    book: BookConfig<'a>,
+}
+
+#[derive(Serialize)]
+struct BookConfig<'a> {
+    title: &'a str,
+    authors: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    src: &'a str,
+}
+
+/// Converts the rendered markdown into the files of an mdBook project,
+/// with one page per chapter
+pub fn mdbook(specification: &Specification, content: &str) -> Vec<(PathBuf, String)> {
+    let metadata = &specification.metadata;
+    let book_toml = BookToml {
+        book: BookConfig {
+            title: &metadata.name,
+            authors: &metadata.authors,
+            description: metadata.description.as_deref(),
+            src: "src",
+        },
+    };
+    let mut files = vec![(
+        PathBuf::from("book.toml"),
+        toml::to_string(&book_toml).expect("couldn't serialize book.toml"),
+    )];
+
+    //~ - the content before the first chapter becomes the introduction (`README.md`)
+    let (introduction, chapters) = split_chapters(content);
+    let mut summary = "# Summary\n\n".to_string();
+    if !introduction.trim().is_empty() {
+        summary.push_str("[Introduction](README.md)\n\n");
+        files.push((PathBuf::from("src/README.md"), introduction));
+    }
+
+    //~ - every chapter gets its own page, listed in `SUMMARY.md`
+    for chapter in chapters {
+        let file = format!("{}.md", chapter.slug);
+        summary.push_str(&format!("- [{}]({file})\n", chapter.title));
+        files.push((PathBuf::from("src").join(file), chapter.content));
+    }
+    files.push((PathBuf::from("src/SUMMARY.md"), summary));
+
+    files
+}
+
+/// The `_category_.json` file of a Docusaurus docs folder
+#[derive(Serialize)]
+struct Category<'a> {
+    label: &'a str,
+    link: CategoryLink<'a>,
+}
+
+#[derive(Serialize)]
+struct CategoryLink<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+}
+
+/// Converts the rendered markdown into a Docusaurus docs folder,
+/// with one page per chapter
+pub fn docusaurus(specification: &Specification, content: &str) -> Vec<(PathBuf, String)> {
+    //~ - the docs folder is a sidebar category named after the specification
+    let metadata = &specification.metadata;
+    let category = Category {
+        label: &metadata.name,
+        link: CategoryLink {
+            kind: "generated-index",
+            description: metadata.description.as_deref(),
+        },
+    };
+    let mut files = vec![(
+        PathBuf::from("_category_.json"),
+        serde_json::to_string_pretty(&category).expect("couldn't serialize _category_.json") + "\n",
+    )];
+
+    //~ - the content before the first chapter becomes the introduction page
+    let (introduction, chapters) = split_chapters(content);
+    let mut position = 1;
+    if !introduction.trim().is_empty() {
+        let front_matter = front_matter(&metadata.name, position, "introduction");
+        files.push((
+            PathBuf::from("introduction.md"),
+            front_matter + &introduction,
+        ));
+        position += 1;
+    }
+
+    //~ - every chapter gets its own page, with a front matter giving
+    //~   its title, position in the sidebar and slug
+    for chapter in chapters {
+        let front_matter = front_matter(&chapter.title, position, &chapter.slug);
+        files.push((
+            PathBuf::from(format!("{}.md", chapter.slug)),
+            front_matter + &chapter.content,
+        ));
+        position += 1;
+    }
+
+    files
+}
+
+/// The YAML front matter of a Docusaurus page
+fn front_matter(title: &str, position: usize, slug: &str) -> String {
+    let title = title.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("---\ntitle: \"{title}\"\nsidebar_position: {position}\nslug: {slug}\n---\n\n")
+}
+
+/// Splits markdown content at its top-level headings,
+/// the shallowest level that is used by more than one heading,
+/// and returns the content before the first chapter along with the chapters
+pub fn split_chapters(content: &str) -> (String, Vec<Chapter>) {
+    let headings = headings(content);
+    let level = (1..=6).find(|level| {
+        headings
+            .iter()
+            .filter(|(_, heading_level, _)| heading_level == level)
+            .count()
+            > 1
+    });
+    let Some(level) = level else {
+        return (content.to_string(), vec![]);
+    };
+
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let starts: Vec<_> = headings
+        .iter()
+        .filter(|(_, heading_level, _)| *heading_level == level)
+        .collect();
+
+    let introduction = lines[..starts[0].0].concat();
+    let mut slugs: HashSet<_> = RESERVED_SLUGS.iter().map(ToString::to_string).collect();
+    let mut chapters = vec![];
+    for (i, (start, _, title)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(lines.len(), |(end, _, _)| *end);
+
+        // make the chapter heading a level-1 heading
+        let mut content = String::new();
+        let mut in_fence = None;
+        for line in &lines[*start..end] {
+            in_fence = fence(line, in_fence);
+            if in_fence.is_none() && heading_level(line).is_some_and(|l| l >= level) {
+                content.push_str(&line[level - 1..]);
+            } else {
+                content.push_str(line);
+            }
+        }
+
+        let mut slug = slugify(title);
+        let base = slug.clone();
+        let mut n = 1;
+        while !slugs.insert(slug.clone()) {
+            n += 1;
+            slug = format!("{base}-{n}");
+        }
+
+        chapters.push(Chapter {
+            title: title.clone(),
+            slug,
+            content,
+        });
+    }
+
+    (introduction, chapters)
+}
+
+/// Lists the (line index, level, title) of the ATX headings outside of code blocks
+fn headings(content: &str) -> Vec<(usize, usize, String)> {
+    let mut headings = vec![];
+    let mut in_fence = None;
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        let was_in_fence = in_fence.is_some();
+        in_fence = fence(line, in_fence);
+        if was_in_fence || in_fence.is_some() {
+            continue;
+        }
+        if let Some(level) = heading_level(line) {
+            let title = line[level..].trim().trim_end_matches('#').trim();
+            headings.push((i, level, title.to_string()));
+        }
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_chapters() {
+        let content = "# Spec\n\nintro\n\n## One\n\ntext\n\n```\n## not a heading\n```\n\n### Sub\n\n## Two\n";
+        let (introduction, chapters) = split_chapters(content);
+
+        assert_eq!(introduction, "# Spec\n\nintro\n\n");
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "One");
+        assert_eq!(chapters[0].slug, "one");
+        assert_eq!(
+            chapters[0].content,
+            "# One\n\ntext\n\n```\n## not a heading\n```\n\n## Sub\n\n"
+        );
+        assert_eq!(chapters[1].content, "# Two\n");
+    }
+
+    #[test]
+    fn test_reserved_and_duplicate_slugs() {
+        let (_, chapters) = split_chapters("# Introduction\n\n# Part\n\n# Part\n");
+        let slugs: Vec<_> = chapters.iter().map(|c| c.slug.as_str()).collect();
+        assert_eq!(slugs, vec!["introduction-2", "part", "part-2"]);
+    }
+
+    #[test]
+    fn test_single_heading_is_not_split() {
+        let (introduction, chapters) = split_chapters("# Spec\n\ntext\n");
+        assert_eq!(introduction, "# Spec\n\ntext\n");
+        assert!(chapters.is_empty());
+    }
+
+    #[test]
+    fn test_front_matter() {
+        assert_eq!(
+            front_matter(r#"The "quoted" \ title"#, 2, "the-quoted-title"),
+            "---\ntitle: \"The \\\"quoted\\\" \\\\ title\"\nsidebar_position: 2\nslug: the-quoted-title\n---\n\n"
+        );
+    }
+}
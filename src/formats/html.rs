@@ -0,0 +1,98 @@
+use crate::toml_parser::Specification;
+use askama::Template;
+use comrak::{markdown_to_html_with_plugins, options::Plugins, plugins::syntect::SyntectAdapter};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use regex::Regex;
+
+/// The syntect theme used to highlight code blocks
+const THEME: &str = "InspiredGitHub";
+
+#[derive(Template)]
+#[template(path = "html.html")]
+struct Html {
+    name: String,
+    description: String,
+    version: String,
+    authors: String,
+    toc: Vec<TocEntry>,
+    content: String,
+}
+
+/// A heading listed in the table of contents
+struct TocEntry {
+    /// The level of the heading, starting at 1
+    level: usize,
+    /// The id of the heading
+    id: String,
+    /// The title of the heading, as HTML
+    title: String,
+}
+
+/// Converts the rendered template to a standalone HTML page,
+/// that doesn't load anything at view time
+pub fn build(specification: &Specification, content: &str) -> Result<String> {
+    //~ - converts markdown content to HTML, highlighting code blocks with inline styles
+    let mut options = super::html_options();
+    options.extension.header_ids = Some(String::new());
+    let adapter = SyntectAdapter::new(Some(THEME));
+    let mut plugins = Plugins::default();
+    plugins.render.codefence_syntax_highlighter = Some(&adapter);
+    let content = markdown_to_html_with_plugins(content, &options, &plugins);
+
+    //~ - generates a table of contents from the headings
+    let toc = table_of_contents(&content);
+
+    //~ - produces the HTML output, with its CSS inlined
+    let metadata = &specification.metadata;
+    let html_page = Html {
+        name: metadata.name.clone(),
+        description: metadata.description.clone().unwrap_or_default(),
+        version: metadata.version.clone().unwrap_or_default(),
+        authors: metadata.authors.join(", "),
+        toc,
+        content,
+    };
+
+    html_page
+        .render()
+        .into_diagnostic()
+        .wrap_err("could not render the HTML page")
+}
+
+/// Lists the headings (with an id) of an HTML document
+fn table_of_contents(html: &str) -> Vec<TocEntry> {
+    let heading = Regex::new(r"(?s)<h([1-6])[^>]*>(.*?)</h[1-6]>").unwrap();
+    let id = Regex::new(r#"id="([^"]*)""#).unwrap();
+    let tag = Regex::new(r"<[^>]*>").unwrap();
+
+    heading
+        .captures_iter(html)
+        .filter_map(|captures| {
+            Some(TocEntry {
+                level: captures[1].parse().unwrap(),
+                id: id.captures(&captures[0])?[1].to_string(),
+                title: tag.replace_all(&captures[2], "").trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_of_contents() {
+        let html = r##"<h1><a href="#intro" aria-hidden="true" class="anchor" id="intro"></a>Intro</h1>
+<p>text</p>
+<h2><a href="#the-code" aria-hidden="true" class="anchor" id="the-code"></a>The <code>code</code></h2>
+"##;
+        let toc = table_of_contents(html);
+
+        assert_eq!(toc.len(), 2);
+        assert_eq!((toc[0].level, toc[0].id.as_str()), (1, "intro"));
+        assert_eq!(toc[0].title, "Intro");
+        assert_eq!((toc[1].level, toc[1].id.as_str()), (2, "the-code"));
+        assert_eq!(toc[1].title, "The code");
+    }
+}
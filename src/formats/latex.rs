@@ -0,0 +1,251 @@
+use crate::toml_parser::Specification;
+use comrak::{
+    nodes::{AstNode, ListType, NodeValue},
+    parse_document, Arena,
+};
+use std::fmt::Write;
+
+/// The preamble of the document, defining the Rust language for listings
+const PREAMBLE: &str = r#"\documentclass{article}
+
+\usepackage[utf8]{inputenc}
+\usepackage[T1]{fontenc}
+\usepackage{amsmath}
+\usepackage{amssymb}
+\usepackage{graphicx}
+\usepackage{listings}
+\usepackage{xcolor}
+\usepackage{hyperref}
+
+\lstdefinelanguage{Rust}{
+  morekeywords={as, break, const, continue, crate, else, enum, extern, false, fn, for, if, impl,
+    in, let, loop, match, mod, move, mut, pub, ref, return, self, Self, static, struct, super,
+    trait, true, type, unsafe, use, where, while, async, await, dyn},
+  sensitive=true,
+  morecomment=[l]{//},
+  morecomment=[s]{/*}{*/},
+  morestring=[b]",
+}
+
+\lstset{
+  basicstyle=\ttfamily\small,
+  keywordstyle=\bfseries,
+  commentstyle=\itshape\color{gray},
+  backgroundcolor=\color{gray!8},
+  breaklines=true,
+  columns=fullflexible,
+  frame=single,
+  rulecolor=\color{gray!40},
+}
+"#;
+
+/// Converts the rendered template to a LaTeX document
+pub fn build(specification: &Specification, content: &str) -> String {
+    //~ - parses the markdown content
+    let arena = Arena::new();
+    let mut options = super::html_options();
+    options.extension.math_dollars = true;
+    let root = parse_document(&arena, content, &options);
+
+    //~ - produces a LaTeX article, titled after the metadata
+    //~   (code blocks become listings, and math is passed through verbatim)
+    let metadata = &specification.metadata;
+    let mut latex = PREAMBLE.to_string();
+    writeln!(latex, "\n\\title{{{}}}", escape(&metadata.name)).unwrap();
+    let authors: Vec<_> = metadata.authors.iter().map(|a| escape(a)).collect();
+    writeln!(latex, "\\author{{{}}}", authors.join(" \\and ")).unwrap();
+    let version = metadata
+        .version
+        .as_deref()
+        .map(|v| format!("Version {}", escape(v)));
+    writeln!(latex, "\\date{{{}}}", version.unwrap_or_default()).unwrap();
+
+    latex.push_str("\n\\begin{document}\n\n\\maketitle\n");
+    if let Some(description) = &metadata.description {
+        writeln!(
+            latex,
+            "\n\\begin{{abstract}}\n{}\n\\end{{abstract}}",
+            escape(description)
+        )
+        .unwrap();
+    }
+    latex.push_str("\n\\tableofcontents\n\n");
+
+    write_node(root, &mut latex);
+
+    latex.push_str("\\end{document}\n");
+    latex
+}
+
+fn write_node<'a>(node: &'a AstNode<'a>, out: &mut String) {
+    let children = |out: &mut String| {
+        for child in node.children() {
+            write_node(child, out);
+        }
+    };
+
+    match &node.data.borrow().value {
+        // blocks
+        NodeValue::Heading(heading) => {
+            let command = match heading.level {
+                1 => "section",
+                2 => "subsection",
+                3 => "subsubsection",
+                4 => "paragraph",
+                _ => "subparagraph",
+            };
+            write!(out, "\\{command}{{").unwrap();
+            children(out);
+            out.push_str("}\n\n");
+        }
+        NodeValue::Paragraph => {
+            children(out);
+            out.push_str("\n\n");
+        }
+        NodeValue::CodeBlock(code) => {
+            let language = code
+                .info
+                .split_whitespace()
+                .next()
+                .and_then(listings_language);
+            out.push_str("\\begin{lstlisting}");
+            if let Some(language) = language {
+                write!(out, "[language={language}]").unwrap();
+            }
+            write!(out, "\n{}\\end{{lstlisting}}\n\n", code.literal).unwrap();
+        }
+        NodeValue::List(list) => {
+            let environment = match list.list_type {
+                ListType::Bullet => "itemize",
+                ListType::Ordered => "enumerate",
+            };
+            writeln!(out, "\\begin{{{environment}}}").unwrap();
+            if list.list_type == ListType::Ordered && list.start != 1 {
+                writeln!(out, "\\setcounter{{enumi}}{{{}}}", list.start - 1).unwrap();
+            }
+            children(out);
+            write!(out, "\\end{{{environment}}}\n\n").unwrap();
+        }
+        NodeValue::Item(_) => {
+            out.push_str("\\item ");
+            children(out);
+        }
+        NodeValue::BlockQuote => {
+            out.push_str("\\begin{quote}\n");
+            children(out);
+            out.push_str("\\end{quote}\n\n");
+        }
+        NodeValue::ThematicBreak => out.push_str("\\noindent\\rule{\\linewidth}{0.4pt}\n\n"),
+        NodeValue::Table(table) => {
+            let columns = "l".repeat(table.alignments.len());
+            writeln!(out, "\\begin{{tabular}}{{|{}|}}\n\\hline", columns).unwrap();
+            children(out);
+            out.push_str("\\end{tabular}\n\n");
+        }
+        NodeValue::TableRow(_) => {
+            for (i, cell) in node.children().enumerate() {
+                if i > 0 {
+                    out.push_str(" & ");
+                }
+                write_node(cell, out);
+            }
+            out.push_str(" \\\\\n\\hline\n");
+        }
+        NodeValue::HtmlBlock(_) | NodeValue::HtmlInline(_) | NodeValue::FrontMatter(_) => (),
+
+        // inlines
+        NodeValue::Text(text) => out.push_str(&escape(text)),
+        NodeValue::Code(code) => write!(out, "\\texttt{{{}}}", escape(&code.literal)).unwrap(),
+        NodeValue::Math(math) if math.display_math => {
+            write!(out, "\\[{}\\]", math.literal).unwrap()
+        }
+        NodeValue::Math(math) => write!(out, "${}$", math.literal).unwrap(),
+        NodeValue::SoftBreak => out.push('\n'),
+        NodeValue::LineBreak => out.push_str("\\\\\n"),
+        NodeValue::Emph => {
+            out.push_str("\\emph{");
+            children(out);
+            out.push('}');
+        }
+        NodeValue::Strong => {
+            out.push_str("\\textbf{");
+            children(out);
+            out.push('}');
+        }
+        NodeValue::Link(link) => {
+            write!(out, "\\href{{{}}}{{", escape_url(&link.url)).unwrap();
+            children(out);
+            out.push('}');
+        }
+        NodeValue::Image(image) => write!(
+            out,
+            "\\includegraphics[width=\\linewidth]{{{}}}",
+            escape_url(&image.url)
+        )
+        .unwrap(),
+
+        _ => children(out),
+    }
+}
+
+/// The name of the listings language of a code block
+fn listings_language(info: &str) -> Option<&'static str> {
+    let language = match info {
+        "rs" | "rust" => "Rust",
+        "c" | "h" => "C",
+        "cpp" | "c++" | "hpp" => "C++",
+        "py" | "python" => "Python",
+        "java" => "Java",
+        "sh" | "bash" => "bash",
+        "sql" => "SQL",
+        "hs" | "haskell" => "Haskell",
+        "ml" | "ocaml" => "ML",
+        "rb" | "ruby" => "Ruby",
+        "tex" | "latex" => "TeX",
+        "xml" | "html" => "XML",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Escapes the characters that have a special meaning in LaTeX
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '<' => escaped.push_str("\\textless{}"),
+            '>' => escaped.push_str("\\textgreater{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes the characters of a URL that have a special meaning in LaTeX
+fn escape_url(url: &str) -> String {
+    url.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('#', "\\#")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a_b & {c} 100%"), r"a\_b \& \{c\} 100\%");
+        assert_eq!(
+            escape(r"x^2 ~ \y"),
+            r"x\textasciicircum{}2 \textasciitilde{} \textbackslash{}y"
+        );
+        assert_eq!(escape("a < b > c"), r"a \textless{} b \textgreater{} c");
+    }
+}
@@ -1,8 +1,6 @@
-use std::{fs::File, io::Write as IOWrite, path::PathBuf};
+use crate::{build::MarkdownFlavor, transformers};
 
-pub fn build(content: &str, output_file: Option<PathBuf>) {
-    let output_file = output_file.unwrap_or_else(|| PathBuf::from("specification.md"));
-    let mut file = File::create(&output_file).unwrap_or_else(|e| panic!("{}", e));
-    write!(&mut file, "{}", content).unwrap();
-    println!("\n=> html output saved at {}", output_file.display());
+/// Applies the markdown flavor to the rendered template
+pub fn build(content: &str, flavor: MarkdownFlavor) -> String {
+    transformers::get_transformer(flavor).transform(content)
 }
@@ -1,2 +1,150 @@
+use crate::{build::Content, errors::SpecError};
+use comrak::options::{Extension, Options, Parse, Render};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+pub mod book;
+pub mod html;
+pub mod latex;
 pub mod markdown;
+pub mod pdf;
 pub mod respec;
+
+/// The output file standing for stdout
+pub const STDOUT: &str = "-";
+
+/// Writes the built specification to the output file (or directory), or to stdout for `-`
+pub fn write(content: &Content, output_file: &Path) -> Result<(), SpecError> {
+    if output_file == Path::new(STDOUT) {
+        return write_to(content, &mut io::stdout().lock());
+    }
+
+    match content {
+        Content::File(content) => {
+            fs::write(output_file, content).map_err(|e| write_error(output_file, e))?;
+        }
+        Content::Directory(files) => {
+            // the pages of chapters that were renamed or removed since the last build
+            for path in stale_files(files, output_file) {
+                fs::remove_file(&path).map_err(|e| write_error(&path, e))?;
+            }
+            for (path, content) in files {
+                let path = output_file.join(path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| write_error(parent, e))?;
+                }
+                fs::write(&path, content).map_err(|e| write_error(&path, e))?;
+            }
+
+            let list = output_file.join(GENERATED_FILES);
+            let generated: String = files
+                .iter()
+                .map(|(path, _)| format!("{}\n", path.display()))
+                .collect();
+            fs::write(&list, generated).map_err(|e| write_error(&list, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a built specification to a stream, which only works for single files
+pub fn write_to(content: &Content, writer: &mut impl io::Write) -> Result<(), SpecError> {
+    match content {
+        Content::File(content) => writer
+            .write_all(content)
+            .and_then(|()| writer.flush())
+            .map_err(SpecError::CantWriteStream),
+        Content::Directory(_) => Err(SpecError::DirectoryToStream),
+    }
+}
+
+/// The file of an output directory listing the files the last build generated in it
+/// (relative to the directory), so that the next builds only ever remove those
+pub const GENERATED_FILES: &str = ".cargo-spec-generated";
+
+/// Lists the files of an output directory that the previous build generated,
+/// but that aren't part of the specification anymore
+/// (as the pages of chapters that were renamed or removed)
+pub fn stale_files(files: &[(PathBuf, String)], output_dir: &Path) -> Vec<PathBuf> {
+    let Ok(previous) = fs::read_to_string(output_dir.join(GENERATED_FILES)) else {
+        return vec![];
+    };
+    let generated: HashSet<_> = files.iter().map(|(path, _)| path.as_path()).collect();
+
+    let mut stale: Vec<_> = previous
+        .lines()
+        .map(Path::new)
+        // only files within the directory, in case the list was edited
+        .filter(|path| {
+            path.components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        })
+        .filter(|path| !path.as_os_str().is_empty() && !generated.contains(path))
+        .map(|path| output_dir.join(path))
+        .filter(|path| path.is_file())
+        .collect();
+    stale.sort();
+    stale
+}
+
+/// The error of a failed write, with a hint for the usual causes
+fn write_error(path: &Path, error: io::Error) -> SpecError {
+    match error.kind() {
+        io::ErrorKind::NotFound => SpecError::MissingOutputDir(
+            path.to_path_buf(),
+            path.parent().unwrap_or(path).display().to_string(),
+        ),
+        io::ErrorKind::PermissionDenied => SpecError::OutputPermissionDenied(path.to_path_buf()),
+        _ => SpecError::CantWriteOutput(path.to_path_buf(), error),
+    }
+}
+
+/// Lists the anchors that links can point to (with `#anchor`) in markdown content:
+/// the ids of its headings, and the ids (or names) of its HTML elements
+pub fn anchors(content: &str) -> HashSet<String> {
+    let mut options = html_options();
+    options.extension.header_ids = Some(String::new());
+    let html = comrak::markdown_to_html(content, &options);
+
+    let anchor = Regex::new(r#"\s(?:id|name)="([^"]*)""#).unwrap();
+    anchor
+        .captures_iter(&html)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// The options used to convert markdown content to HTML
+fn html_options<'c>() -> Options<'c> {
+    Options {
+        extension: Extension {
+            strikethrough: true,
+            tagfilter: true,
+            table: true,
+            autolink: true,
+            tasklist: true,
+            superscript: true,
+            header_ids: None,
+            footnotes: true,
+            description_lists: true,
+            front_matter_delimiter: None,
+            ..Default::default()
+        },
+        parse: Parse {
+            smart: true,
+            default_info_string: None,
+            ..Default::default()
+        },
+        render: Render {
+            hardbreaks: false,
+            github_pre_lang: true,
+            width: 0,
+            r#unsafe: true, // it's our spec afterall
+            escape: false,
+            ..Default::default()
+        },
+    }
+}
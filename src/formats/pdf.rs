@@ -0,0 +1,535 @@
+use crate::{errors::SpecError, toml_parser::Specification};
+use comrak::{
+    nodes::{AstNode, ListType, NodeValue},
+    parse_document, Arena,
+};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use printpdf::{
+    lopdf, BuiltinFont, Color, IndirectFontRef, Line, Mm, OffsetDateTime, PdfDocument,
+    PdfDocumentReference, PdfLayerReference, PdfPageIndex, Point, Rect, Rgb,
+};
+use regex::bytes::Regex;
+use std::collections::BTreeSet;
+
+/// A4 page, in mm
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+
+/// Font sizes, in points
+const TITLE_SIZE: f32 = 22.0;
+const TEXT_SIZE: f32 = 10.5;
+const CODE_SIZE: f32 = 9.0;
+
+/// The size of a point, in mm
+const PT: f32 = 0.3528;
+
+/// The indentation of lists and block quotes, in mm
+const INDENT: f32 = 6.0;
+
+/// The padding around code blocks and table cells, in mm
+const PADDING: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Regular,
+    Bold,
+    Italic,
+    Mono,
+}
+
+/// A piece of text laid out on a line, with its offset from the start of the line (in mm)
+type Piece = (String, Style, f32);
+
+/// Converts the rendered template to a PDF document, using the fonts built in every PDF reader
+pub fn build(specification: &Specification, content: &str) -> Result<Vec<u8>> {
+    //~ - parses the markdown content
+    let arena = Arena::new();
+    let mut options = super::html_options();
+    options.extension.math_dollars = true;
+    let root = parse_document(&arena, content, &options);
+
+    //~ - lays out the metadata and the content on A4 pages
+    //~   (math is written as its TeX source)
+    let metadata = &specification.metadata;
+    let mut writer = Writer::new(&metadata.name);
+    let width = PAGE_WIDTH - 2.0 * MARGIN;
+
+    writer.paragraph(
+        &[(metadata.name.clone(), Style::Bold)],
+        TITLE_SIZE,
+        MARGIN,
+        width,
+    );
+    let mut subtitle = vec![];
+    if let Some(version) = &metadata.version {
+        subtitle.push(format!("Version {version}"));
+    }
+    if !metadata.authors.is_empty() {
+        subtitle.push(metadata.authors.join(", "));
+    }
+    subtitle.extend(metadata.description.clone());
+    for line in subtitle {
+        writer.paragraph(&[(line, Style::Italic)], TEXT_SIZE, MARGIN, width);
+    }
+    writer.rule(MARGIN, width);
+
+    writer.block(root, MARGIN, width);
+
+    //~ - fails if the content uses characters that the built-in fonts can't render
+    //~   (they only cover Latin-1 and a few typographic characters)
+    if !writer.missing.is_empty() {
+        let characters: Vec<_> = writer
+            .missing
+            .iter()
+            .map(|c| format!("{c} (U+{:04X})", *c as u32))
+            .collect();
+        return Err(SpecError::UnsupportedPdfCharacters(characters.join(", ")).into());
+    }
+
+    //~ - produces the PDF output, dated from `SOURCE_DATE_EPOCH` (or the Unix epoch)
+    //~   and identified by a hash of its content, so that the same specification
+    //~   always gives the same file
+    let date = source_date();
+    let doc = writer
+        .doc
+        .with_author(metadata.authors.join(", "))
+        .with_creation_date(date)
+        .with_mod_date(date)
+        .with_metadata_date(date);
+    let doc = match &metadata.description {
+        Some(description) => doc.with_subject(description),
+        None => doc,
+    };
+    let bytes = doc
+        .save_to_bytes()
+        .into_diagnostic()
+        .wrap_err("could not save the PDF document")?;
+    Ok(with_content_id(bytes))
+}
+
+/// The date of the document: `SOURCE_DATE_EPOCH` if set, for reproducible builds,
+/// the Unix epoch otherwise
+fn source_date() -> OffsetDateTime {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .and_then(|epoch| OffsetDateTime::from_unix_timestamp(epoch).ok())
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+/// Replaces the random identifiers printpdf writes in the trailer of the document
+/// with a hash of the document (of the same length, to keep the offsets of the file valid)
+fn with_content_id(mut bytes: Vec<u8>) -> Vec<u8> {
+    let trailer_id = Regex::new(r"/ID\[\(\w{32}\)\(\w{32}\)\]").unwrap();
+    let Some(found) = trailer_id.find_iter(&bytes).last() else {
+        return bytes;
+    };
+
+    let content = &bytes[..found.start()];
+    let id = format!(
+        "{:016X}{:016X}",
+        fnv1a(content, 0xcbf29ce484222325),
+        fnv1a(content, 0x84222325cbf29ce4)
+    );
+    let replacement = format!("/ID[({id})({id})]");
+    bytes.splice(found.range(), replacement.into_bytes());
+    bytes
+}
+
+/// The FNV-1a hash of some bytes, which (unlike the hasher of the standard library)
+/// is the same for every version of Rust
+fn fnv1a(bytes: &[u8], offset_basis: u64) -> u64 {
+    bytes.iter().fold(offset_basis, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Writes blocks of content on pages, from top to bottom
+struct Writer {
+    doc: PdfDocumentReference,
+    /// The fonts, indexed by [Style]
+    fonts: [IndirectFontRef; 4],
+    page: PdfPageIndex,
+    layer: PdfLayerReference,
+    /// The position of the cursor from the bottom of the page, in mm
+    y: f32,
+    /// The characters written that the fonts can't render
+    missing: BTreeSet<char>,
+}
+
+impl Writer {
+    fn new(title: &str) -> Self {
+        let (doc, page, layer) =
+            PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "content");
+        let fonts = [
+            BuiltinFont::Helvetica,
+            BuiltinFont::HelveticaBold,
+            BuiltinFont::HelveticaOblique,
+            BuiltinFont::Courier,
+        ]
+        .map(|font| doc.add_builtin_font(font).unwrap());
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Self {
+            doc,
+            fonts,
+            page,
+            layer,
+            y: PAGE_HEIGHT - MARGIN,
+            missing: BTreeSet::new(),
+        }
+    }
+
+    fn font(&self, style: Style) -> &IndirectFontRef {
+        &self.fonts[style as usize]
+    }
+
+    /// Moves the cursor down, starting a new page if there isn't enough room left
+    fn advance(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            let (page, layer) = self
+                .doc
+                .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "content");
+            self.page = page;
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+        self.y -= height;
+    }
+
+    /// Writes a line of text, below the cursor
+    fn line(&mut self, pieces: &[Piece], size: f32, x: f32) {
+        self.advance(line_height(size));
+        let baseline = self.y + size * PT * 0.35;
+        for (text, style, offset) in pieces {
+            self.missing
+                .extend(text.chars().filter(|c| !c.is_control() && !renderable(*c)));
+            self.layer
+                .use_text(text, size, Mm(x + offset), Mm(baseline), self.font(*style));
+        }
+    }
+
+    /// Writes a paragraph, wrapping its text
+    fn paragraph(&mut self, spans: &[(String, Style)], size: f32, x: f32, width: f32) {
+        for line in wrap(spans, size, width) {
+            self.line(&line, size, x);
+        }
+        self.y -= size * PT * 0.6;
+    }
+
+    /// Draws a horizontal line below the cursor
+    fn rule(&mut self, x: f32, width: f32) {
+        self.advance(3.0);
+        self.horizontal_line(x, width, self.y + 1.5);
+        self.y -= 2.0;
+    }
+
+    fn horizontal_line(&self, x: f32, width: f32, y: f32) {
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(x), Mm(y)), false),
+                (Point::new(Mm(x + width), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    fn block<'a>(&mut self, node: &'a AstNode<'a>, x: f32, width: f32) {
+        match &node.data.borrow().value {
+            NodeValue::Heading(heading) => {
+                let size = match heading.level {
+                    1 => 18.0,
+                    2 => 15.0,
+                    3 => 13.0,
+                    _ => 11.5,
+                };
+                let spans = inlines(node, Style::Bold);
+                let title: String = spans.iter().map(|(text, _)| text.as_str()).collect();
+
+                // keep the heading with the start of its content
+                self.advance(size * PT + line_height(TEXT_SIZE) * 3.0);
+                self.y += line_height(TEXT_SIZE) * 3.0;
+                self.doc.add_bookmark(title, self.page);
+                self.paragraph(&spans, size, x, width);
+            }
+            NodeValue::Paragraph => {
+                self.paragraph(&inlines(node, Style::Regular), TEXT_SIZE, x, width)
+            }
+            NodeValue::CodeBlock(code) => self.code(&code.literal, x, width),
+            NodeValue::List(list) => {
+                for (i, item) in node.children().enumerate() {
+                    let marker = match list.list_type {
+                        ListType::Bullet => "\u{2022}".to_string(),
+                        ListType::Ordered => format!("{}.", list.start + i),
+                    };
+                    self.advance(line_height(TEXT_SIZE));
+                    self.y += line_height(TEXT_SIZE);
+                    let baseline = self.y - line_height(TEXT_SIZE) + TEXT_SIZE * PT * 0.35;
+                    self.layer.use_text(
+                        marker,
+                        TEXT_SIZE,
+                        Mm(x),
+                        Mm(baseline),
+                        self.font(Style::Regular),
+                    );
+                    for child in item.children() {
+                        self.block(child, x + INDENT, width - INDENT);
+                    }
+                }
+            }
+            NodeValue::BlockQuote => {
+                for child in node.children() {
+                    self.block(child, x + INDENT, width - INDENT);
+                }
+            }
+            NodeValue::Table(_) => self.table(node, x, width),
+            NodeValue::ThematicBreak => self.rule(x, width),
+            NodeValue::HtmlBlock(_) | NodeValue::FrontMatter(_) => (),
+            _ => {
+                for child in node.children() {
+                    self.block(child, x, width);
+                }
+            }
+        }
+    }
+
+    /// Writes a code block on a grey background, breaking lines that don't fit
+    fn code(&mut self, code: &str, x: f32, width: f32) {
+        let columns = ((width - 2.0 * PADDING) / char_width(Style::Mono, CODE_SIZE)) as usize;
+        let height = line_height(CODE_SIZE);
+
+        for line in code.trim_end_matches('\n').split('\n') {
+            let chars: Vec<char> = line.replace('\t', "    ").chars().collect();
+            for chunk in chars
+                .chunks(columns.max(1))
+                .chain(chars.is_empty().then_some(&[][..]))
+            {
+                self.advance(height);
+                self.layer
+                    .set_fill_color(Color::Rgb(Rgb::new(0.95, 0.95, 0.95, None)));
+                self.layer.add_rect(Rect::new(
+                    Mm(x),
+                    Mm(self.y),
+                    Mm(x + width),
+                    Mm(self.y + height),
+                ));
+                self.layer
+                    .set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+                self.y += height;
+                let text: String = chunk.iter().collect();
+                self.line(&[(text, Style::Mono, 0.0)], CODE_SIZE, x + PADDING);
+            }
+        }
+        self.y -= TEXT_SIZE * PT * 0.6;
+    }
+
+    /// Writes a table whose columns share the width equally, the header in bold
+    fn table<'a>(&mut self, table: &'a AstNode<'a>, x: f32, width: f32) {
+        let columns = table
+            .children()
+            .map(|row| row.children().count())
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        let column_width = width / columns as f32;
+        let height = line_height(TEXT_SIZE);
+
+        for (r, row) in table.children().enumerate() {
+            let header = matches!(row.data.borrow().value, NodeValue::TableRow(true));
+            let style = if header { Style::Bold } else { Style::Regular };
+            let cells: Vec<_> = row
+                .children()
+                .map(|cell| {
+                    wrap(
+                        &inlines(cell, style),
+                        TEXT_SIZE,
+                        column_width - 2.0 * PADDING,
+                    )
+                })
+                .collect();
+            let lines = cells.iter().map(Vec::len).max().unwrap_or(1);
+
+            self.advance(lines as f32 * height + PADDING);
+            let top = self.y + lines as f32 * height + PADDING;
+            if r == 0 {
+                self.horizontal_line(x, width, top);
+            }
+            for (i, cell) in cells.iter().enumerate() {
+                self.y = top - PADDING / 2.0;
+                for line in cell {
+                    self.line(line, TEXT_SIZE, x + i as f32 * column_width + PADDING);
+                }
+            }
+            self.y = top - lines as f32 * height - PADDING;
+            self.horizontal_line(x, width, self.y);
+        }
+        self.y -= TEXT_SIZE * PT * 0.6;
+    }
+}
+
+/// Collects the text of the inline children of a node
+fn inlines<'a>(node: &'a AstNode<'a>, style: Style) -> Vec<(String, Style)> {
+    let mut spans = vec![];
+    collect_inlines(node, style, &mut spans);
+    spans
+}
+
+fn collect_inlines<'a>(node: &'a AstNode<'a>, style: Style, spans: &mut Vec<(String, Style)>) {
+    for child in node.children() {
+        match &child.data.borrow().value {
+            NodeValue::Text(text) => spans.push((text.to_string(), style)),
+            NodeValue::Code(code) => spans.push((code.literal.to_string(), Style::Mono)),
+            NodeValue::Math(math) => spans.push((math.literal.to_string(), Style::Mono)),
+            NodeValue::SoftBreak => spans.push((" ".to_string(), style)),
+            NodeValue::LineBreak => spans.push(("\n".to_string(), style)),
+            NodeValue::HtmlInline(_) => (),
+            NodeValue::Emph => collect_inlines(child, Style::Italic, spans),
+            NodeValue::Strong => collect_inlines(child, Style::Bold, spans),
+            _ => collect_inlines(child, style, spans),
+        }
+    }
+}
+
+/// Lays out spans of text on lines of at most `width` mm
+fn wrap(spans: &[(String, Style)], size: f32, width: f32) -> Vec<Vec<Piece>> {
+    let mut lines = vec![vec![]];
+    let mut offset = 0.0;
+
+    for (text, style) in spans {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(vec![]);
+                offset = 0.0;
+            }
+            for word in part.split_inclusive(' ') {
+                if offset > 0.0 && offset + text_width(word.trim_end(), *style, size) > width {
+                    lines.push(vec![]);
+                    offset = 0.0;
+                }
+                if word.trim().is_empty() {
+                    if offset > 0.0 {
+                        offset += text_width(word, *style, size);
+                    }
+                    continue;
+                }
+                lines
+                    .last_mut()
+                    .unwrap()
+                    .push((word.to_string(), *style, offset));
+                offset += text_width(word, *style, size);
+            }
+        }
+    }
+
+    lines
+}
+
+/// Whether a character is in the WinAnsi encoding of the built-in fonts
+/// (printpdf silently drops the other ones)
+fn renderable(c: char) -> bool {
+    let mut buffer = [0; 4];
+    !lopdf::Document::encode_text(Some("WinAnsiEncoding"), c.encode_utf8(&mut buffer)).is_empty()
+}
+
+fn line_height(size: f32) -> f32 {
+    size * PT * 1.4
+}
+
+/// The width of a text, in mm
+fn text_width(text: &str, style: Style, size: f32) -> f32 {
+    text.chars().map(|c| char_width_of(c, style, size)).sum()
+}
+
+/// The width of a character of a monospace font, in mm
+fn char_width(style: Style, size: f32) -> f32 {
+    char_width_of(' ', style, size)
+}
+
+fn char_width_of(c: char, style: Style, size: f32) -> f32 {
+    let units = match style {
+        Style::Mono => 600.0,
+        // bold characters are a bit wider
+        Style::Bold => helvetica_width(c) * 1.07,
+        Style::Regular | Style::Italic => helvetica_width(c),
+    };
+    units / 1000.0 * size * PT
+}
+
+/// The width of the printable ASCII characters in Helvetica,
+/// in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278,
+    278, // ' ' to /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, // 0 to 9
+    278, 278, 584, 584, 584, 556, 1015, // : to @
+    667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, // A to M
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, // N to Z
+    278, 278, 278, 469, 556, 333, // [ to `
+    556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, // a to m
+    556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, // n to z
+    334, 260, 334, 584, // { to ~
+];
+
+fn helvetica_width(c: char) -> f32 {
+    match c {
+        ' '..='~' => HELVETICA_WIDTHS[c as usize - ' ' as usize] as f32,
+        _ => 556.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let spans = [
+            ("some ".to_string(), Style::Regular),
+            ("code".to_string(), Style::Mono),
+            (" and more words".to_string(), Style::Regular),
+        ];
+        let width = text_width("some  and", Style::Regular, TEXT_SIZE)
+            + text_width("code", Style::Mono, TEXT_SIZE);
+        let lines = wrap(&spans, TEXT_SIZE, width);
+
+        let words: Vec<Vec<&str>> = lines
+            .iter()
+            .map(|line| line.iter().map(|(text, _, _)| text.trim_end()).collect())
+            .collect();
+        assert_eq!(
+            words,
+            vec![vec!["some", "code", "and"], vec!["more", "words"]]
+        );
+        assert_eq!(lines[0][1].1, Style::Mono);
+        assert_eq!(lines[1][0].2, 0.0);
+    }
+
+    #[test]
+    fn test_content_id() {
+        let pdf = b"%PDF-1.3\n1 0 obj\n/ID[(ABCDEFGHIJABCDEFGHIJABCDEFGHIJAB)(BCDEFGHIJABCDEFGHIJABCDEFGHIJABC)]".to_vec();
+        let other = b"%PDF-1.3\n1 0 obj\n/ID[(JIHGFEDCBAJIHGFEDCBAJIHGFEDCBAJI)(IHGFEDCBAJIHGFEDCBAJIHGFEDCBAJIH)]".to_vec();
+
+        let len = pdf.len();
+        let (pdf, other) = (with_content_id(pdf), with_content_id(other));
+        assert_eq!(pdf, other);
+        assert_eq!(pdf.len(), len);
+        assert!(!pdf.ends_with(b"(BCDEFGHIJABCDEFGHIJABCDEFGHIJABC)]"));
+    }
+
+    #[test]
+    fn test_unsupported_characters() {
+        let specification: Specification = toml::from_str(
+            "[metadata]\nname = \"Test\"\nauthors = []\n[config]\ntemplate = \"t.md\"\n[sections]\n",
+        )
+        .unwrap();
+
+        assert!(build(&specification, "# Café\n\n“Quotes” – and • bullets…\n").is_ok());
+
+        let error = build(&specification, "# ∑ 日本\n\n```\nx ∈ S\n```\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The PDF fonts can't render these characters: ∈ (U+2208), ∑ (U+2211), 日 (U+65E5), 本 (U+672C)"
+        );
+    }
+}
@@ -1,11 +1,7 @@
-use askama::Template;
-use comrak::{
-    markdown_to_html,
-    options::{Extension, Options, Parse, Render},
-};
-use std::{fs::File, io::Write as IOWrite, path::PathBuf};
-
 use crate::toml_parser::Specification;
+use askama::Template;
+use comrak::markdown_to_html;
+use miette::{IntoDiagnostic, Result, WrapErr};
 
 #[derive(Template)]
 #[template(path = "respec.html", escape = "none")]
@@ -18,39 +14,10 @@ struct Respec {
     content: String,
 }
 
-pub fn build(specification: &Specification, content: &str, output_file: Option<PathBuf>) {
-    let output_file = output_file.unwrap_or_else(|| PathBuf::from("specification.html"));
-
+/// Converts the rendered template to a respec HTML page
+pub fn build(specification: &Specification, content: &str) -> Result<String> {
     //~ - converts markdown content to pure HTML
-    let options = Options {
-        extension: Extension {
-            strikethrough: true,
-            tagfilter: true,
-            table: true,
-            autolink: true,
-            tasklist: true,
-            superscript: true,
-            header_ids: None,
-            footnotes: true,
-            description_lists: true,
-            front_matter_delimiter: None,
-            ..Default::default()
-        },
-        parse: Parse {
-            smart: true,
-            default_info_string: None,
-            ..Default::default()
-        },
-        render: Render {
-            hardbreaks: false,
-            github_pre_lang: true,
-            width: 0,
-            r#unsafe: true, // it's our spec afterall
-            escape: false,
-            ..Default::default()
-        },
-    };
-    let content = markdown_to_html(content, &options);
+    let content = markdown_to_html(content, &super::html_options());
 
     //~ - produces the HTML output
     let html_page = Respec {
@@ -72,7 +39,8 @@ pub fn build(specification: &Specification, content: &str, output_file: Option<P
         content,
     };
 
-    let mut file = File::create(&output_file).unwrap_or_else(|e| panic!("{}", e));
-    write!(&mut file, "{}", html_page.render().unwrap()).unwrap();
-    println!("\n=> html output saved at {}", output_file.display());
+    html_page
+        .render()
+        .into_diagnostic()
+        .wrap_err("could not render the respec page")
 }
@@ -1,17 +1,123 @@
-#![allow(dead_code, unused_variables)]
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-use std::{path::Path, process::Command};
+use crate::errors::SpecError;
 
-fn get_github_url(filepath: &Path, line: usize) -> Option<String> {
-    let local_repo = get_local_repo_path()?;
-    let github_repo = get_github_repo()?;
+/// How to link each extracted block back to the code that defines it
+#[derive(Debug, Clone)]
+pub struct SourceLinks {
+    /// where the links point to
+    kind: SourceLinksKind,
+    /// the commit the links are pinned to
+    commit: String,
+    /// the root of the local repository
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+enum SourceLinksKind {
+    /// `https://github.com/<owner>/<repo>/blob/<commit>/<path>#L<line>`
+    Github(String),
 
-    None
+    /// `https://gitlab.com/<owner>/<repo>/-/blob/<commit>/<path>#L<line>`
+    Gitlab(String),
+
+    /// A user-provided URL pattern containing `{commit}`, `{path}` and `{line}`
+    Pattern(String),
 }
 
-/// runs `git remote -v` and checks if it's a github URL
-fn get_github_repo() -> Option<String> {
-    let res = Command::new("git").args(["remote", "-v"]).output().ok()?;
+impl SourceLinks {
+    /// Creates source links from the `source_links` value of the specification config,
+    /// which is either `github`, `gitlab`, or a URL pattern.
+    /// Git is queried from `dir`, which should be within the repository.
+    pub fn new(config: &str, dir: &Path) -> Result<Self, SpecError> {
+        // anything else than a known host must be a pattern, or the links would all be broken
+        let is_pattern = config.contains("{commit}") && config.contains("{path}");
+        if !is_pattern && config != "github" && config != "gitlab" {
+            return Err(SpecError::BadSourceLinks(config.to_string()));
+        }
+
+        let root = git(dir, &["rev-parse", "--show-toplevel"])
+            .and_then(|root| std::fs::canonicalize(root).ok())
+            .ok_or(SpecError::NoGitCommit)?;
+        let commit = git(dir, &["rev-parse", "HEAD"]).ok_or(SpecError::NoGitCommit)?;
+        let remote =
+            || git(dir, &["remote", "get-url", "origin"]).and_then(|r| remote_to_web_url(&r));
+
+        let kind = match config {
+            "github" => SourceLinksKind::Github(
+                remote()
+                    .filter(|url| url.starts_with("https://github.com/"))
+                    .ok_or(SpecError::NoGitRemote(config.to_string()))?,
+            ),
+            "gitlab" => SourceLinksKind::Gitlab(
+                remote()
+                    .filter(|url| url.contains("gitlab"))
+                    .ok_or(SpecError::NoGitRemote(config.to_string()))?,
+            ),
+            pattern => SourceLinksKind::Pattern(pattern.to_string()),
+        };
+
+        Ok(Self { kind, commit, root })
+    }
+
+    /// Returns the URL pointing at `line` (starting at 1) of the file at `filepath`,
+    /// or `None` if the file is not part of the local repository.
+    pub fn url(&self, filepath: &Path, line: usize) -> Option<String> {
+        let filepath = std::fs::canonicalize(filepath).ok()?;
+        let path = filepath.strip_prefix(&self.root).ok()?;
+        let path = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let commit = &self.commit;
+        let url = match &self.kind {
+            SourceLinksKind::Github(repo) => format!("{repo}/blob/{commit}/{path}#L{line}"),
+            SourceLinksKind::Gitlab(repo) => format!("{repo}/-/blob/{commit}/{path}#L{line}"),
+            SourceLinksKind::Pattern(pattern) => pattern
+                .replace("{commit}", commit)
+                .replace("{path}", &path)
+                .replace("{line}", &line.to_string()),
+        };
+
+        Some(url)
+    }
+
+    /// Replaces the commit of the source links found in `content` with `{commit}`,
+    /// whatever commit they're pinned to, so that specifications built at different commits
+    /// can be compared (other commit hashes of the content are left as they are)
+    pub fn unpin<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        let url = match &self.kind {
+            SourceLinksKind::Github(repo) => format!("{repo}/blob/{{commit}}/"),
+            SourceLinksKind::Gitlab(repo) => format!("{repo}/-/blob/{{commit}}/"),
+            SourceLinksKind::Pattern(pattern) => pattern.clone(),
+        };
+        let Some((before_commit, _)) = url.split_once("{commit}") else {
+            return Cow::Borrowed(content);
+        };
+
+        // the part of the links before the commit
+        let prefix = regex::escape(before_commit)
+            .replace(r"\{path\}", r"[^\s)]*?")
+            .replace(r"\{line\}", r"\d+");
+        let link =
+            regex::Regex::new(&format!(r"({prefix})[0-9a-f]{{{}}}\b", self.commit.len())).unwrap();
+        link.replace_all(content, "${1}{commit}")
+    }
+}
+
+/// runs git with the given arguments in `dir` and returns its trimmed output
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let res = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .ok()?;
 
     if !res.status.success() {
         return None;
@@ -19,7 +125,24 @@ fn get_github_repo() -> Option<String> {
 
     let res = String::from_utf8(res.stdout).ok()?;
 
-    None
+    Some(res.trim().to_string())
+}
+
+/// converts `git@host:owner/repo.git` or `https://host/owner/repo.git` into `https://host/owner/repo`
+fn remote_to_web_url(remote: &str) -> Option<String> {
+    let url = if let Some(rest) = remote.strip_prefix("git@") {
+        let (host, path) = rest.split_once(':')?;
+        format!("https://{host}/{path}")
+    } else if let Some(rest) = remote.strip_prefix("ssh://git@") {
+        format!("https://{rest}")
+    } else if remote.starts_with("https://") || remote.starts_with("http://") {
+        remote.to_string()
+    } else {
+        return None;
+    };
+
+    let url = url.trim_end_matches('/');
+    Some(url.strip_suffix(".git").unwrap_or(url).to_string())
 }
 
 /// runs `git rev-parse --show-toplevel` to get filepath of root
@@ -37,3 +160,56 @@ pub fn get_local_repo_path() -> Option<String> {
 
     Some(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_to_web_url() {
+        assert_eq!(
+            remote_to_web_url("git@github.com:o1-labs/cargo-specification.git").as_deref(),
+            Some("https://github.com/o1-labs/cargo-specification")
+        );
+        assert_eq!(
+            remote_to_web_url("https://github.com/o1-labs/cargo-specification").as_deref(),
+            Some("https://github.com/o1-labs/cargo-specification")
+        );
+        assert_eq!(
+            remote_to_web_url("ssh://git@gitlab.com/group/project.git").as_deref(),
+            Some("https://gitlab.com/group/project")
+        );
+        assert_eq!(remote_to_web_url("/some/local/path"), None);
+    }
+
+    #[test]
+    fn test_unpin() {
+        let commit = "0123456789abcdef0123456789abcdef01234567";
+        let links = |kind| SourceLinks {
+            kind,
+            commit: commit.to_string(),
+            root: PathBuf::new(),
+        };
+        let other = "fedcba9876543210fedcba9876543210fedcba98";
+
+        let github = links(SourceLinksKind::Github(
+            "https://github.com/o/r".to_string(),
+        ));
+        assert_eq!(
+            github.unpin(&format!(
+                "[source](https://github.com/o/r/blob/{other}/a.rs#L1) {other}"
+            )),
+            format!("[source](https://github.com/o/r/blob/{{commit}}/a.rs#L1) {other}")
+        );
+
+        let pattern = links(SourceLinksKind::Pattern(
+            "https://host/{path}?at={commit}#{line}".to_string(),
+        ));
+        assert_eq!(
+            pattern.unpin(&format!(
+                "[source](https://host/src/a.rs?at={other}#3) {other}"
+            )),
+            format!("[source](https://host/src/a.rs?at={{commit}}#3) {other}")
+        );
+    }
+}
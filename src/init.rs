@@ -27,7 +27,7 @@ pub fn init(name: Option<String>, path: PathBuf) -> Result<()> {
         match path.file_name() {
             Some(dir_name) => dir_name.to_string_lossy().to_string(),
             None => {
-                return Err(SpecError::BadPath(path)).into_diagnostic();
+                return Err(SpecError::BadPath(path).into());
             }
         }
     };
@@ -49,7 +49,7 @@ pub fn init(name: Option<String>, path: PathBuf) -> Result<()> {
             let template_file_detected =
                 dir_entry.file_name().to_string_lossy() == DEFAULT_TEMPLATE;
             if spec_file_detected || template_file_detected {
-                return Err(SpecError::SpecAlreadyExists(path)).into_diagnostic();
+                return Err(SpecError::SpecAlreadyExists(path).into());
             }
         }
     }
@@ -70,12 +70,14 @@ pub fn init(name: Option<String>, path: PathBuf) -> Result<()> {
     };
     let config = Config {
         template: DEFAULT_TEMPLATE.to_string(),
+        source_links: None,
     };
     let specification = Specification {
         metadata,
         config,
         output: None,
         sections: HashMap::new(),
+        languages: HashMap::new(),
     };
 
     let manifest_content =
@@ -1,8 +1,42 @@
+//! cargo-spec extracts spec comments from code and renders them into a specification,
+//! following a template.
+//!
+//! Besides the `cargo spec` command, it can be used as a library,
+//! to build specifications from a `build.rs` script or other tools:
+//!
+//! ```no_run
+//! use cargo_spec::{Content, SpecBuilder};
+//!
+//! let builder = SpecBuilder::from_file("Specification.toml")?;
+//!
+//! // the spec comments of a single file
+//! for block in builder.extract("src/lib.rs")? {
+//!     println!("line {}: {}", block.line, block.content);
+//! }
+//!
+//! // the whole specification
+//! if let Content::File(markdown) = builder.render()? {
+//!     println!("{}", String::from_utf8_lossy(&markdown));
+//! }
+//! # Ok::<(), miette::Report>(())
+//! ```
+
 pub mod build;
 mod comment_parser;
 mod errors;
+mod filters;
 mod formats;
 mod git;
 pub mod init;
+pub mod lint;
+mod markdown;
+mod rust_parser;
+pub mod serve;
+mod template;
 pub mod toml_parser;
 pub mod transformers;
+pub mod workspace;
+
+pub use build::{Content, MarkdownFlavor, OutputFormat, SpecBuilder};
+pub use comment_parser::SpecBlock;
+pub use toml_parser::Specification;
@@ -0,0 +1,421 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use miette::{IntoDiagnostic, NamedSource, Result, WrapErr};
+use regex::Regex;
+
+use crate::{
+    build::{relative_display, render_all, section_files, split_root, Content, OutputFormat},
+    comment_parser::{self, SpecBlock},
+    errors::SpecError,
+    formats,
+    git::get_local_repo_path,
+    markdown::{fence, heading_level},
+    toml_parser::{self, Manifest},
+};
+
+/// A problem in spec comments
+#[derive(Debug, PartialEq, Eq)]
+enum Problem {
+    /// A link to a file that doesn't exist (or to nothing)
+    BrokenLink,
+    /// A link to an anchor that the rendered specification doesn't have
+    MissingLinkAnchor,
+    /// A heading more than one level deeper than the previous one
+    HeadingJump(usize, usize),
+    /// A code fence that is never closed
+    UnbalancedFence,
+    /// Whitespace at the end of a line
+    TrailingWhitespace,
+    /// A `$` (or `$$`) starting math that is never closed
+    UnmatchedDollar,
+}
+
+/// A problem, along with where it is in the content of a block
+#[derive(Debug, PartialEq, Eq)]
+struct Finding {
+    problem: Problem,
+    /// The line of the content (starting at 0)
+    line: usize,
+    /// The offset of the problem within the line
+    column: usize,
+    /// The length of the problem
+    len: usize,
+}
+
+/// What linting the specifications found
+pub struct Linted {
+    /// The problems found in the spec comments
+    pub problems: Vec<miette::Report>,
+}
+
+impl Linted {
+    /// Returns an error if any problem was found
+    pub fn no_problems(&self) -> Result<()> {
+        if !self.problems.is_empty() {
+            return Err(SpecError::LintProblems(self.problems.len()).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Lints the spec comments of every file listed in the sections of the specifications,
+/// returning the problems found
+pub fn lint(toml_spec: PathBuf) -> Result<Linted> {
+    // links to anchors are checked against the rendered specifications
+    let anchors: HashSet<String> = render_all(&toml_spec, None, OutputFormat::Markdown, None)?
+        .iter()
+        .flat_map(|rendered| match &rendered.content {
+            Content::File(content) => formats::anchors(&String::from_utf8_lossy(content)),
+            Content::Directory(_) => unreachable!("markdown is a single file"),
+        })
+        .collect();
+
+    let mut spec_dir = fs::canonicalize(&toml_spec)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            format!(
+                "could not find the specification file {}",
+                toml_spec.display()
+            )
+        })?;
+    spec_dir.pop();
+
+    let specifications = match toml_parser::parse_manifest(&toml_spec)? {
+        Manifest::Single(specification) => vec![*specification],
+        Manifest::Multiple(entries, _) => entries
+            .into_iter()
+            .map(|entry| entry.specification)
+            .collect(),
+    };
+
+    // a file listed by several sections is only linted once
+    let base = get_local_repo_path();
+    let mut files = vec![];
+    for specification in &specifications {
+        for (section, filename) in &specification.sections {
+            let (root, relative) = split_root(filename, &spec_dir, base.as_deref())?;
+            let relative = relative
+                .rsplit_once('#')
+                .map_or(relative.as_str(), |(relative, _)| relative);
+            for path in section_files(section, &root, relative, &specification.languages)?.files {
+                files.push((path, &specification.languages));
+            }
+        }
+    }
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    files.dedup_by(|(a, _), (b, _)| a == b);
+
+    let mut linted = Linted { problems: vec![] };
+    for (path, languages) in files {
+        let blocks = comment_parser::parse_file(&path, languages)?;
+        let source = fs::read_to_string(&path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("could not read file {}", path.display()))?;
+        let name = relative_display(&path, &spec_dir);
+        let link_exists = |link: &str| spec_dir.join(link).exists();
+
+        let problems = lint_file(Path::new(&name), &source, &blocks, link_exists, &anchors);
+        linted
+            .problems
+            .extend(problems.into_iter().map(miette::Report::new));
+    }
+
+    Ok(linted)
+}
+
+/// Lints the blocks of spec comments extracted from a file,
+/// where `link_exists` tells if a relative link points to an existing file,
+/// and `anchors` are the anchors of the rendered specification
+fn lint_file(
+    path: &Path,
+    source: &str,
+    blocks: &[SpecBlock],
+    link_exists: impl Fn(&str) -> bool,
+    anchors: &HashSet<String>,
+) -> Vec<SpecError> {
+    let source_lines: Vec<_> = source.split_inclusive('\n').collect();
+    let line_offsets: Vec<_> = source_lines
+        .iter()
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len();
+            Some(start)
+        })
+        .collect();
+
+    let mut problems = vec![];
+    for block in blocks {
+        let content_lines: Vec<_> = block.content.lines().collect();
+        let lines = locate_lines(&source_lines, block.line - 1, &content_lines);
+
+        for finding in lint_block(&block.content, &link_exists, anchors) {
+            // point at the problem in the source file, or at the start of the block
+            let content_line = content_lines.get(finding.line).copied().unwrap_or_default();
+            let span = lines
+                .get(finding.line)
+                .copied()
+                .flatten()
+                .and_then(|line| {
+                    let source_line = source_lines[line];
+                    let start = source_line.find(content_line.trim_start())?;
+                    let indentation = content_line.len() - content_line.trim_start().len();
+                    let column = (start + finding.column).checked_sub(indentation)?;
+                    Some((line_offsets[line] + column, finding.len))
+                })
+                .unwrap_or((line_offsets.get(block.line - 1).copied().unwrap_or(0), 0));
+
+            let src = NamedSource::new(path.to_string_lossy(), source.to_string());
+            problems.push(match finding.problem {
+                Problem::BrokenLink => SpecError::BrokenLink {
+                    _src: src,
+                    _bad_bit: span,
+                },
+                Problem::MissingLinkAnchor => SpecError::MissingLinkAnchor {
+                    _src: src,
+                    _bad_bit: span,
+                },
+                Problem::HeadingJump(from, to) => SpecError::HeadingJump {
+                    _src: src,
+                    _bad_bit: span,
+                    from,
+                    to,
+                },
+                Problem::UnbalancedFence => SpecError::UnbalancedFence {
+                    _src: src,
+                    _bad_bit: span,
+                },
+                Problem::TrailingWhitespace => SpecError::TrailingWhitespace {
+                    _src: src,
+                    _bad_bit: span,
+                },
+                Problem::UnmatchedDollar => SpecError::UnmatchedDollar {
+                    _src: src,
+                    _bad_bit: span,
+                },
+            });
+        }
+    }
+    problems
+}
+
+/// Finds the line of the source file that each line of a block comes from,
+/// by looking for their text from the first line of the block
+/// (lines that were generated, like the fences around extracted code, have none)
+fn locate_lines(source_lines: &[&str], start: usize, content_lines: &[&str]) -> Vec<Option<usize>> {
+    let mut next = start;
+    content_lines
+        .iter()
+        .map(|line| {
+            let text = line.trim();
+            let found = if text.is_empty() {
+                (next < source_lines.len()).then_some(next)
+            } else {
+                (next..source_lines.len()).find(|i| source_lines[*i].contains(text))
+            };
+            if let Some(found) = found {
+                next = found + 1;
+            }
+            found
+        })
+        .collect()
+}
+
+/// Lints markdown content
+fn lint_block(
+    content: &str,
+    link_exists: impl Fn(&str) -> bool,
+    anchors: &HashSet<String>,
+) -> Vec<Finding> {
+    let link = Regex::new(r#"\]\(\s*([^)\s]*)(?:\s+"[^"]*")?\s*\)"#).unwrap();
+
+    let mut findings = vec![];
+    let mut find = |problem, line, column, len| {
+        findings.push(Finding {
+            problem,
+            line,
+            column,
+            len,
+        })
+    };
+
+    let mut in_fence = None;
+    let mut fence_start = (0, 0, 0);
+    let mut last_heading = None;
+    let mut display_math = None;
+    for (i, line) in content.lines().enumerate() {
+        let was_in_fence = in_fence.is_some();
+        in_fence = fence(line, in_fence);
+        if was_in_fence || in_fence.is_some() {
+            if !was_in_fence {
+                let indentation = line.len() - line.trim_start().len();
+                fence_start = (i, indentation, line.trim().len());
+            }
+            continue;
+        }
+
+        let trimmed = line.trim_end();
+        if trimmed.len() < line.len() {
+            find(
+                Problem::TrailingWhitespace,
+                i,
+                trimmed.len(),
+                line.len() - trimmed.len(),
+            );
+        }
+
+        if let Some(level) = heading_level(line) {
+            if let Some(last) = last_heading.filter(|last| level > last + 1) {
+                find(Problem::HeadingJump(last, level), i, 0, level);
+            }
+            last_heading = Some(level);
+        }
+
+        // what's within inline code can't be a link or math
+        let text = mask_code_spans(line);
+
+        for captures in link.captures_iter(&text) {
+            let target = captures.get(1).unwrap();
+            let problem = match target.as_str() {
+                "" => Some(Problem::BrokenLink),
+                url if url.contains("://") || url.starts_with("mailto:") => None,
+                url => match url.split_once('#') {
+                    Some(("", anchor)) => {
+                        (!anchors.contains(anchor)).then_some(Problem::MissingLinkAnchor)
+                    }
+                    Some((path, _)) => (!link_exists(path)).then_some(Problem::BrokenLink),
+                    None => (!link_exists(url)).then_some(Problem::BrokenLink),
+                },
+            };
+            if let Some(problem) = problem {
+                let whole = captures.get(0).unwrap();
+                find(problem, i, whole.start() + 1, whole.len() - 1);
+            }
+        }
+
+        let text = text.replace("\\$", "  ");
+        let mut inline_math = None;
+        let mut chars = text.char_indices().peekable();
+        while let Some((column, c)) = chars.next() {
+            if c != '$' {
+                continue;
+            }
+            if chars.next_if(|(_, c)| *c == '$').is_some() {
+                display_math = match display_math {
+                    Some(_) => None,
+                    None => Some((i, column)),
+                };
+            } else if display_math.is_none() {
+                inline_math = match inline_math {
+                    Some(_) => None,
+                    None => Some(column),
+                };
+            }
+        }
+        if let Some(column) = inline_math {
+            find(Problem::UnmatchedDollar, i, column, 1);
+        }
+    }
+
+    if in_fence.is_some() {
+        let (line, column, len) = fence_start;
+        find(Problem::UnbalancedFence, line, column, len);
+    }
+    if let Some((line, column)) = display_math {
+        find(Problem::UnmatchedDollar, line, column, 2);
+    }
+
+    findings
+}
+
+/// Replaces the inline code of a line with spaces, keeping the offsets of the rest
+fn mask_code_spans(line: &str) -> String {
+    // the (offset, length) of the runs of backticks
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for (i, c) in line.char_indices() {
+        match runs.last_mut() {
+            Some((start, len)) if c == '`' && *start + *len == i => *len += 1,
+            _ if c == '`' => runs.push((i, 1)),
+            _ => (),
+        }
+    }
+
+    // inline code ends with a run of as many backticks as the one starting it
+    let mut masked = line.to_string();
+    let mut i = 0;
+    while i < runs.len() {
+        let (start, len) = runs[i];
+        match (i + 1..runs.len()).find(|j| runs[*j].1 == len) {
+            Some(j) => {
+                let end = runs[j].0 + len;
+                masked.replace_range(start..end, &" ".repeat(end - start));
+                i = j + 1;
+            }
+            None => i += 1,
+        }
+    }
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(content: &str) -> Vec<(Problem, usize, usize, usize)> {
+        let anchors = HashSet::from(["intro".to_string()]);
+        lint_block(content, |path| path == "exists.md", &anchors)
+            .into_iter()
+            .map(|f| (f.problem, f.line, f.column, f.len))
+            .collect()
+    }
+
+    #[test]
+    fn test_links() {
+        assert_eq!(
+            lint("[a](exists.md) [b](missing.md) [c](#intro) [d](#nope) [e](https://x.y)"),
+            vec![
+                (Problem::BrokenLink, 0, 18, 12),
+                (Problem::MissingLinkAnchor, 0, 46, 7),
+            ]
+        );
+        assert_eq!(
+            lint("`[a](missing.md)` [b]()"),
+            vec![(Problem::BrokenLink, 0, 21, 2)]
+        );
+    }
+
+    #[test]
+    fn test_headings_and_whitespace() {
+        assert_eq!(
+            lint("# A\n\n### B  \n## C\n"),
+            vec![
+                (Problem::TrailingWhitespace, 2, 5, 2),
+                (Problem::HeadingJump(1, 3), 2, 0, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fences_and_math() {
+        assert_eq!(
+            lint("$x$ and `$` and \\$5\n\n$$\nx\n$$\n\n```\n$ code\n"),
+            vec![(Problem::UnbalancedFence, 6, 0, 3)]
+        );
+        assert_eq!(
+            lint("costs $5\n\n$$\nx"),
+            vec![
+                (Problem::UnmatchedDollar, 0, 6, 1),
+                (Problem::UnmatchedDollar, 2, 0, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mask_code_spans() {
+        assert_eq!(mask_code_spans("a `b` c ``d`e`` `f"), "a     c         `f");
+    }
+}
@@ -1,11 +1,13 @@
 use cargo_spec::{
-    build::{build, watch, MarkdownFlavor, OutputFormat},
+    build::{build, check, watch, Built, Checked, MarkdownFlavor, OutputFormat, STDOUT},
     init::{init, new, DEFAULT_MANIFEST, DEFAULT_TEMPLATE},
-    toml_parser,
+    lint::lint,
+    serve::serve,
+    workspace::{self, Member},
 };
 use clap::{Args, Parser, Subcommand};
-use miette::Result;
-use std::path::PathBuf;
+use miette::{IntoDiagnostic, Result};
+use std::path::{Path, PathBuf};
 
 /// To make cargo subcommands work, you need to use `bin_name`,
 /// as well as a subcommand.
@@ -30,11 +32,40 @@ enum Spec {
     },
 
     /// Create the specification file at the given path.
-    Build(Opt),
+    Build(BuildOpt),
+
+    /// Checks that the specification file at the given path is up to date,
+    /// printing a diff if it isn't.
+    Check(BuildOpt),
 
     /// Watches any listed files in the specification toml file and
     /// re-create the specification on any changes.
     Watch(Opt),
+
+    /// Serves the specification as an HTML page on localhost,
+    /// reloading it in the browser whenever it is rebuilt.
+    Serve {
+        /// The path to the specification toml file (defaults to Specification.toml).
+        #[clap(short, long, value_name = "SPEC_PATH")]
+        specification_path: Option<PathBuf>,
+
+        /// The port to serve the specification on
+        #[clap(short, long, default_value_t = 3000)]
+        port: u16,
+
+        /// The markdown flavor to use (mdbook or docusaurus)
+        #[clap(short = 'F', long, value_name = "MARKDOWN_FLAVOR")]
+        #[clap(value_enum)]
+        flavor: Option<MarkdownFlavor>,
+    },
+
+    /// Lints the spec comments of the files listed in the specification toml file
+    /// (broken links, heading jumps, unbalanced code fences, trailing whitespace, unmatched `$`).
+    Lint {
+        /// The path to the specification toml file (defaults to Specification.toml).
+        #[clap(short, long, value_name = "SPEC_PATH")]
+        specification_path: Option<PathBuf>,
+    },
 }
 
 /// The different options that can be passed to this CLI
@@ -45,8 +76,8 @@ struct Opt {
     #[clap(short, long, value_name = "SPEC_PATH")]
     specification_path: Option<PathBuf>,
 
-    /// The path to the specification file to write
-    /// (defaults to specification.md or specification.html)
+    /// The path to the specification file (or directory, for books) to write,
+    /// or `-` for stdout (defaults to specification.md or specification.html)
     #[clap(short, long, value_name = "OUTPUT_FILE")]
     output_file: Option<PathBuf>,
 
@@ -61,33 +92,50 @@ struct Opt {
     flavor: Option<MarkdownFlavor>,
 }
 
-/// Resolve the markdown flavor from CLI, config file, or default.
-/// Priority: CLI flag > config file > default (Mdbook)
-fn resolve_flavor(
-    cli_flavor: Option<MarkdownFlavor>,
-    toml_spec: &std::path::Path,
-) -> Result<MarkdownFlavor> {
-    // CLI flag takes precedence
-    if let Some(flavor) = cli_flavor {
-        return Ok(flavor);
+/// The options of the commands that can run on a whole workspace
+#[derive(Args, Debug)]
+struct BuildOpt {
+    #[clap(flatten)]
+    opt: Opt,
+
+    /// Run on every member of the cargo workspace that has a Specification.toml
+    /// (the output file is then relative to each member)
+    #[clap(long, conflicts_with = "specification_path")]
+    workspace: bool,
+}
+
+/// Prints the problems that didn't prevent building the specifications
+fn print_warnings(warnings: &[miette::Report]) {
+    for warning in warnings {
+        eprintln!("{warning:?}");
+    }
+}
+
+/// Prints where the specifications were saved (unless they were written to stdout)
+fn print_built(built: &Built) {
+    for output_file in &built.output_files {
+        if output_file != Path::new(STDOUT) {
+            println!("\n=> specification saved at {}", output_file.display());
+        }
     }
+}
 
-    // Try to read from config file
-    if toml_spec.exists() {
-        let spec = toml_parser::parse_toml_spec(toml_spec)?;
-        if let Some(output_config) = spec.output {
-            if let Some(flavor_str) = output_config.flavor {
-                return match flavor_str.to_lowercase().as_str() {
-                    "mdbook" => Ok(MarkdownFlavor::Mdbook),
-                    "docusaurus" => Ok(MarkdownFlavor::Docusaurus),
-                    _ => Ok(MarkdownFlavor::default()),
-                };
-            }
+/// Prints the specifications that are up to date, and how the others differ
+fn print_checked(checked: &Checked) {
+    for (output_file, differences) in &checked.output_files {
+        if differences.is_empty() {
+            println!("=> {} is up to date", output_file.display());
+        }
+        for difference in differences {
+            println!("{difference}");
         }
     }
+}
 
-    // Default
-    Ok(MarkdownFlavor::default())
+/// Runs a command on every member of the current workspace that has a specification
+fn for_each_member(command: impl FnMut(&Member) -> Result<()>) -> Result<()> {
+    let members = workspace::members(&std::env::current_dir().into_diagnostic()?)?;
+    workspace::for_each_member(&members, command)
 }
 
 fn main() -> Result<()> {
@@ -114,20 +162,90 @@ fn main() -> Result<()> {
         }
 
         //~   a. the `Build` mode builds the specification
-        Spec::Build(Opt {
-            specification_path,
-            output_file,
-            output_format,
-            flavor,
+        //~      (or the specifications of all the members of a workspace, with `--workspace`)
+        Spec::Build(BuildOpt {
+            opt:
+                Opt {
+                    output_file,
+                    output_format,
+                    flavor,
+                    ..
+                },
+            workspace: true,
+        }) => {
+            workspace::reject_stdout("build --workspace", output_file.as_deref())?;
+            let output_format = output_format.unwrap_or(OutputFormat::Markdown);
+            for_each_member(|member| {
+                let toml_spec = member.specification();
+                let output_file = member.output_file(output_file.clone(), output_format)?;
+                let built = build(toml_spec, output_file, output_format, flavor)?;
+                print_warnings(&built.warnings);
+                print_built(&built);
+                Ok(())
+            })?;
+        }
+
+        Spec::Build(BuildOpt {
+            opt:
+                Opt {
+                    specification_path,
+                    output_file,
+                    output_format,
+                    flavor,
+                },
+            ..
+        }) => {
+            let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
+            let output_format = output_format.unwrap_or(OutputFormat::Markdown);
+
+            let built = build(toml_spec, output_file, output_format, flavor)?;
+            print_warnings(&built.warnings);
+            print_built(&built);
+        }
+
+        //~   b. the `Check` mode makes sure that the specification is up to date
+        Spec::Check(BuildOpt {
+            opt:
+                Opt {
+                    output_file,
+                    output_format,
+                    flavor,
+                    ..
+                },
+            workspace: true,
+        }) => {
+            workspace::reject_stdout("check --workspace", output_file.as_deref())?;
+            let output_format = output_format.unwrap_or(OutputFormat::Markdown);
+            for_each_member(|member| {
+                let toml_spec = member.specification();
+                let output_file = member.output_file(output_file.clone(), output_format)?;
+                let checked = check(toml_spec, output_file, output_format, flavor)?;
+                print_warnings(&checked.warnings);
+                print_checked(&checked);
+                checked.up_to_date()
+            })?;
+        }
+
+        Spec::Check(BuildOpt {
+            opt:
+                Opt {
+                    specification_path,
+                    output_file,
+                    output_format,
+                    flavor,
+                },
+            ..
         }) => {
             let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
             let output_format = output_format.unwrap_or(OutputFormat::Markdown);
-            let flavor = resolve_flavor(flavor, &toml_spec)?;
 
-            let _ = build(toml_spec, output_file, output_format, flavor)?;
+            let checked = check(toml_spec, output_file, output_format, flavor)?;
+            print_warnings(&checked.warnings);
+            print_checked(&checked);
+            checked.up_to_date()?;
         }
 
-        //~   b. the `Watch` mode builds the specification on every change
+        //~   c. the `Watch` mode builds the specification on every change
         Spec::Watch(Opt {
             specification_path,
             output_file,
@@ -136,9 +254,31 @@ fn main() -> Result<()> {
         }) => {
             let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
             let output_format = output_format.unwrap_or(OutputFormat::Markdown);
-            let flavor = resolve_flavor(flavor, &toml_spec)?;
 
-            watch(toml_spec, output_format, output_file, flavor);
+            watch(toml_spec, output_format, output_file, flavor)?;
+        }
+
+        //~   d. the `Serve` mode serves the specification as HTML, and reloads it on every change
+        Spec::Serve {
+            specification_path,
+            port,
+            flavor,
+        } => {
+            let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
+
+            serve(toml_spec, port, flavor)?;
+        }
+
+        //~   e. the `Lint` mode reports problems in the spec comments
+        Spec::Lint { specification_path } => {
+            let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
+            let linted = lint(toml_spec)?;
+            for problem in &linted.problems {
+                eprintln!("{problem:?}");
+            }
+            linted.no_problems()?;
+
+            println!("=> no problems found");
         }
     };
 
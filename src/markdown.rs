@@ -0,0 +1,58 @@
+/// The level of a line's ATX heading (`# title`), if it is one
+pub fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.trim().is_empty() || rest.starts_with([' ', '\t'])))
+        .then_some(level)
+}
+
+/// Returns the code fence that is open after `line`,
+/// given the one that was open before it
+pub fn fence(line: &str, open: Option<String>) -> Option<String> {
+    let trimmed = line.trim_start();
+    let marker: String = trimmed
+        .chars()
+        .take_while(|c| *c == '`' || *c == '~')
+        .collect();
+    let is_fence = marker.len() >= 3 && marker.chars().all(|c| c == marker.chars().next().unwrap());
+
+    match open {
+        Some(open) if is_fence && marker.starts_with(&open) && trimmed.trim() == marker => None,
+        Some(open) => Some(open),
+        None if is_fence => Some(marker),
+        None => None,
+    }
+}
+
+/// Turns a title into a lowercase name made of letters, digits and dashes
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "chapter".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Toml parser"), "toml-parser");
+        assert_eq!(
+            slugify("The `spec:item` instruction!"),
+            "the-spec-item-instruction"
+        );
+        assert_eq!(slugify("?!"), "chapter");
+    }
+}
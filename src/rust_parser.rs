@@ -0,0 +1,271 @@
+use proc_macro2::LineColumn;
+use syn::{spanned::Spanned, ImplItem, Item, TraitItem, Type};
+
+/// A Rust item (struct, enum, function, trait, etc.) found in a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustItem {
+    /// The name of the item, if it has one (impl blocks don't)
+    pub name: Option<String>,
+    /// The modules, types (of impl blocks) and traits the item is nested in, outermost first
+    pub parents: Vec<String>,
+    /// The line (starting at 1) where the item starts, including its attributes
+    pub line: usize,
+    /// The source of the item, or only its signature for functions
+    pub source: String,
+}
+
+/// Parses a Rust file and returns all of its items,
+/// including the ones within modules, impl blocks and traits.
+pub fn parse_items(source: &str) -> Result<Vec<RustItem>, syn::Error> {
+    let file = syn::parse_file(source)?;
+    let mut items = vec![];
+    collect_items(source, &file.items, &[], &mut items);
+    Ok(items)
+}
+
+/// Returns the first item starting after the given line (starting at 1)
+pub fn next_item(items: &[RustItem], line: usize) -> Option<&RustItem> {
+    items
+        .iter()
+        .filter(|item| item.line > line)
+        .min_by_key(|item| item.line)
+}
+
+impl RustItem {
+    /// The path of the item within its file, as in `Type::method`
+    pub fn path(&self) -> String {
+        let mut path = self.parents.clone();
+        path.extend(self.name.clone());
+        path.join("::")
+    }
+}
+
+/// Returns the item with the given name, or path within the file (as in `Type::method`),
+/// or all the items matching it if there isn't exactly one
+pub fn find_item<'a>(items: &'a [RustItem], path: &str) -> Result<&'a RustItem, Vec<&'a RustItem>> {
+    let mut segments: Vec<_> = path
+        .split("::")
+        .map(|segment| segment.trim().to_string())
+        .collect();
+    let name = segments.pop();
+    let candidates: Vec<_> = items
+        .iter()
+        .filter(|item| item.name == name && item.parents.ends_with(&segments))
+        .collect();
+    match candidates.as_slice() {
+        [item] => Ok(item),
+        _ => Err(candidates),
+    }
+}
+
+/// Returns the byte offset of `line_column` in `source`
+pub fn offset(source: &str, line_column: LineColumn) -> usize {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(line_column.line - 1)
+        .map(str::len)
+        .sum();
+
+    let column = source[line_start..]
+        .char_indices()
+        .nth(line_column.column)
+        .map_or(source.len() - line_start, |(column, _)| column);
+
+    line_start + column
+}
+
+/// Collects the items of a module, given the names of the items it's nested in (`parents`)
+fn collect_items(source: &str, items: &[Item], parents: &[String], result: &mut Vec<RustItem>) {
+    for item in items {
+        let (name, end) = match item {
+            Item::Const(item) => (Some(&item.ident), None),
+            Item::Enum(item) => (Some(&item.ident), None),
+            Item::Fn(item) => (Some(&item.sig.ident), Some(item.sig.span().end())),
+            Item::Macro(item) => (item.ident.as_ref(), None),
+            Item::Mod(item) => (Some(&item.ident), None),
+            Item::Static(item) => (Some(&item.ident), None),
+            Item::Struct(item) => (Some(&item.ident), None),
+            Item::Trait(item) => (Some(&item.ident), None),
+            Item::TraitAlias(item) => (Some(&item.ident), None),
+            Item::Type(item) => (Some(&item.ident), None),
+            Item::Union(item) => (Some(&item.ident), None),
+            Item::Impl(_) => (None, None),
+            _ => continue,
+        };
+        result.push(extract(source, item.span(), end, name, parents));
+
+        // look for nested items
+        match item {
+            Item::Mod(item) => {
+                if let Some((_, items)) = &item.content {
+                    let parents = [parents, &[item.ident.to_string()]].concat();
+                    collect_items(source, items, &parents, result);
+                }
+            }
+            Item::Impl(item) => {
+                let mut parents = parents.to_vec();
+                parents.extend(type_name(&item.self_ty));
+                for impl_item in &item.items {
+                    let (name, end) = match impl_item {
+                        ImplItem::Const(item) => (&item.ident, None),
+                        ImplItem::Fn(item) => (&item.sig.ident, Some(item.sig.span().end())),
+                        ImplItem::Type(item) => (&item.ident, None),
+                        _ => continue,
+                    };
+                    result.push(extract(source, impl_item.span(), end, Some(name), &parents));
+                }
+            }
+            Item::Trait(item) => {
+                let parents = [parents, &[item.ident.to_string()]].concat();
+                for trait_item in &item.items {
+                    let (name, end) = match trait_item {
+                        TraitItem::Const(item) => (&item.ident, None),
+                        TraitItem::Fn(item) => (&item.sig.ident, Some(item.sig.span().end())),
+                        TraitItem::Type(item) => (&item.ident, None),
+                        _ => continue,
+                    };
+                    result.push(extract(
+                        source,
+                        trait_item.span(),
+                        end,
+                        Some(name),
+                        &parents,
+                    ));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The name of the type of an impl block (`Point` for `impl<T> Trait for a::Point<T>`)
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Extracts the source of an item, up to `end` if given (instead of the end of the item),
+/// removing the indentation of the item from every line
+fn extract(
+    source: &str,
+    span: proc_macro2::Span,
+    end: Option<LineColumn>,
+    name: Option<&syn::Ident>,
+    parents: &[String],
+) -> RustItem {
+    let start = span.start();
+    let end = end.unwrap_or_else(|| span.end());
+    let text = &source[offset(source, start)..offset(source, end)];
+
+    let mut lines = text.lines();
+    let mut result = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        let left_trimmed = line.trim_start();
+        let whitespaces_len = line.len() - left_trimmed.len();
+        result.push('\n');
+        if start.column > whitespaces_len {
+            result.push_str(left_trimmed);
+        } else {
+            result.push_str(&line[start.column..]);
+        }
+    }
+
+    RustItem {
+        name: name.map(ToString::to_string),
+        parents: parents.to_vec(),
+        line: start.line,
+        source: result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"use std::fmt;
+
+/// A point
+#[derive(Debug)]
+pub struct Point {
+    x: u8,
+}
+
+impl Point {
+    /// Creates a point
+    pub fn new(x: u8) -> Self {
+        Self { x }
+    }
+}
+
+mod inner {
+    pub enum Kind {
+        A,
+        B,
+    }
+
+    pub fn new() -> Kind {
+        Kind::A
+    }
+}
+"#;
+
+    #[test]
+    fn test_struct_with_attributes() {
+        let items = parse_items(SOURCE).unwrap();
+        let item = find_item(&items, "Point").unwrap();
+        assert_eq!(item.line, 3);
+        assert_eq!(
+            item.source,
+            "/// A point\n#[derive(Debug)]\npub struct Point {\n    x: u8,\n}"
+        );
+    }
+
+    #[test]
+    fn test_function_signature_only() {
+        let items = parse_items(SOURCE).unwrap();
+        let item = find_item(&items, "Point::new").unwrap();
+        assert_eq!(
+            item.source,
+            "/// Creates a point\npub fn new(x: u8) -> Self"
+        );
+    }
+
+    #[test]
+    fn test_nested_item_is_dedented() {
+        let items = parse_items(SOURCE).unwrap();
+        let item = find_item(&items, "Kind").unwrap();
+        assert_eq!(item.source, "pub enum Kind {\n    A,\n    B,\n}");
+    }
+
+    #[test]
+    fn test_find_item_by_path() {
+        let items = parse_items(SOURCE).unwrap();
+        let paths = |candidates: Vec<&RustItem>| -> Vec<String> {
+            candidates.iter().map(|item| item.path()).collect()
+        };
+        assert_eq!(
+            paths(find_item(&items, "new").unwrap_err()),
+            vec!["Point::new", "inner::new"]
+        );
+        assert_eq!(find_item(&items, "inner::new").unwrap().line, 22);
+        assert_eq!(find_item(&items, "inner::Kind").unwrap().line, 17);
+        assert_eq!(
+            paths(find_item(&items, "Point::Kind").unwrap_err()),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_next_item() {
+        let items = parse_items(SOURCE).unwrap();
+        assert_eq!(next_item(&items, 1).unwrap().name.as_deref(), Some("Point"));
+        assert_eq!(next_item(&items, 9).unwrap().name.as_deref(), Some("new"));
+        assert_eq!(next_item(&items, 22), None);
+    }
+}
@@ -0,0 +1,278 @@
+use miette::Result;
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Write,
+    path::{Component, Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{
+    build::{
+        render_all_with, watch_with, Built, Content, ExtractionCache, MarkdownFlavor, OutputFormat,
+    },
+    errors::SpecError,
+};
+
+/// The script added to every page, reloading it when the server sends an event
+const RELOAD_SCRIPT: &str =
+    r#"<script>new EventSource("/events").onmessage = () => location.reload();</script>"#;
+
+/// What the server serves
+#[derive(Default)]
+struct State {
+    /// the HTML pages of the last successful build, by path
+    pages: HashMap<String, String>,
+    /// the error of the last build, shown instead of the pages until it's fixed
+    error: Option<String>,
+    /// the pages opened in a browser, waiting for a reload
+    listeners: Vec<Sender<()>>,
+}
+
+impl State {
+    /// The page to serve at a given path, if any
+    fn page(&self, path: &str) -> Option<String> {
+        if let Some(error) = &self.error {
+            return Some(error.clone());
+        }
+
+        match path {
+            // a single specification is served at the root, several are listed there
+            "/" if self.pages.len() == 1 => self.pages.values().next().cloned(),
+            "/" => {
+                let mut paths: Vec<_> = self.pages.keys().collect();
+                paths.sort();
+                let links: String = paths
+                    .into_iter()
+                    .map(|path| format!("<li><a href=\"{path}\">{}</a></li>", &path[1..]))
+                    .collect();
+                Some(with_reload_script(&format!(
+                    "<html><body><h1>Specifications</h1><ul>{links}</ul></body></html>"
+                )))
+            }
+            path => self.pages.get(path).cloned(),
+        }
+    }
+}
+
+/// Serves the specifications as HTML pages on localhost, rebuilding them on every change
+/// of the files they are built from, and reloading them in the browser
+pub fn serve(toml_spec: PathBuf, port: u16, flavor: Option<MarkdownFlavor>) -> Result<()> {
+    let server = Server::http(("127.0.0.1", port)).map_err(|e| SpecError::CantServe(port, e))?;
+    let state = Arc::new(Mutex::new(State::default()));
+    println!("=> serving the specification at http://localhost:{port}");
+
+    // output files are relative to the specification file
+    let spec_dir = fs::canonicalize(&toml_spec)
+        .ok()
+        .and_then(|path| Some(path.parent()?.to_path_buf()))
+        .unwrap_or_default();
+
+    let mut server = Some(server);
+    let mut cache = ExtractionCache::default();
+    watch_with(&toml_spec, |changed| {
+        cache.invalidate(changed);
+        let rendered = render_all_with(&toml_spec, None, OutputFormat::Html, flavor, &mut cache);
+
+        // requests wait for the first build to be answered
+        if let Some(server) = server.take() {
+            let state = state.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(request, &state);
+                }
+            });
+        }
+
+        let mut state = state.lock().unwrap();
+        let built = rendered.and_then(|rendered| {
+            let mut pages = HashMap::new();
+            let mut built = Built {
+                output_files: vec![],
+                files_to_watch: HashSet::new(),
+                warnings: vec![],
+            };
+            for rendered in rendered {
+                let path = page_path(&rendered.output_file, &spec_dir);
+                if pages
+                    .insert(path.clone(), page(&rendered.content))
+                    .is_some()
+                {
+                    return Err(SpecError::DuplicatePage(path).into());
+                }
+                built.files_to_watch.extend(rendered.files_to_watch);
+                built.warnings.extend(rendered.warnings);
+            }
+            Ok((pages, built))
+        });
+        let built = match built {
+            Ok((pages, built)) => {
+                state.error = None;
+                state.pages = pages;
+                Ok(built)
+            }
+            Err(e) => {
+                state.error = Some(error_page(&e));
+                Err(e)
+            }
+        };
+
+        // reload the opened pages (forgetting the ones that were closed)
+        state.listeners.retain(|listener| listener.send(()).is_ok());
+
+        built
+    })
+}
+
+/// The path a specification is served at, after its output file relative to `spec_dir`
+fn page_path(output_file: &Path, spec_dir: &Path) -> String {
+    let relative = output_file.strip_prefix(spec_dir).unwrap_or(output_file);
+    let segments: Vec<_> = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(segment) => Some(segment.to_string_lossy()),
+            _ => None,
+        })
+        .collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// The HTML page of a specification, reloading itself on every rebuild
+fn page(content: &Content) -> String {
+    match content {
+        Content::File(html) => with_reload_script(&String::from_utf8_lossy(html)),
+        Content::Directory(_) => unreachable!("HTML is a single file"),
+    }
+}
+
+/// A page showing a build error, reloading itself on every rebuild
+fn error_page(error: &miette::Report) -> String {
+    // the report is colored for terminals
+    let colors = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    let report = format!("{error:?}");
+    let report = colors
+        .replace_all(&report, "")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    with_reload_script(&format!(
+        "<html><body><h1>The specification can't be built</h1><pre>{report}</pre></body></html>"
+    ))
+}
+
+/// Adds the [RELOAD_SCRIPT] at the end of the body of a page
+fn with_reload_script(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{RELOAD_SCRIPT}\n{}", &html[..end], &html[end..]),
+        None => format!("{html}{RELOAD_SCRIPT}\n"),
+    }
+}
+
+/// Responds to a request for a page, or for the events telling it to reload
+fn respond(request: Request, state: &Mutex<State>) {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    if path == "/events" {
+        let (tx, rx) = channel();
+        state.lock().unwrap().listeners.push(tx);
+        thread::spawn(move || send_events(request, rx));
+        return;
+    }
+
+    let page = state.lock().unwrap().page(&path);
+    let html = Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap();
+    // the browser might not wait for the response
+    let _ = match page {
+        Some(page) => request.respond(Response::from_string(page).with_header(html)),
+        None => request.respond(Response::from_string("Not found").with_status_code(404)),
+    };
+}
+
+/// Streams server-sent events to a page, telling it to reload on every rebuild,
+/// until it's closed
+fn send_events(request: Request, rebuilds: Receiver<()>) {
+    // the stream is written as is, to send every event as soon as possible
+    let mut writer = request.into_writer();
+    let headers =
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n";
+    if writer
+        .write_all(headers.as_bytes())
+        .and_then(|()| writer.flush())
+        .is_err()
+    {
+        return;
+    }
+
+    for () in rebuilds {
+        let sent = writer
+            .write_all(b"data: reload\n\n")
+            .and_then(|()| writer.flush());
+        if sent.is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_reload_script() {
+        assert_eq!(
+            with_reload_script("<html><body>a</body></html>"),
+            format!("<html><body>a{RELOAD_SCRIPT}\n</body></html>")
+        );
+        assert_eq!(with_reload_script("a"), format!("a{RELOAD_SCRIPT}\n"));
+    }
+
+    #[test]
+    fn test_page_path() {
+        let spec_dir = Path::new("/spec");
+        assert_eq!(
+            page_path(Path::new("specification.html"), spec_dir),
+            "/specification.html"
+        );
+        assert_eq!(
+            page_path(Path::new("/spec/a/spec.html"), spec_dir),
+            "/a/spec.html"
+        );
+        assert_eq!(
+            page_path(Path::new("/spec/b/spec.html"), spec_dir),
+            "/b/spec.html"
+        );
+        assert_eq!(
+            page_path(Path::new("/spec/../docs/c.html"), spec_dir),
+            "/docs/c.html"
+        );
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut state = State::default();
+        state.pages.insert("/a.html".to_string(), "A".to_string());
+        assert_eq!(state.page("/").as_deref(), Some("A"));
+        assert_eq!(state.page("/a.html").as_deref(), Some("A"));
+        assert_eq!(state.page("/b.html"), None);
+
+        state.pages.insert("/b.html".to_string(), "B".to_string());
+        assert!(state
+            .page("/")
+            .unwrap()
+            .contains("<a href=\"/b.html\">b.html</a>"));
+
+        state.error = Some("error".to_string());
+        assert_eq!(state.page("/a.html").as_deref(), Some("error"));
+    }
+}
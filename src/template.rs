@@ -0,0 +1,439 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use miette::{IntoDiagnostic, NamedSource, Result, WrapErr};
+
+use crate::{errors::SpecError, rust_parser, toml_parser::Language};
+
+/// The start of a directive embedding the definition of a Rust item,
+/// as in `{code "src/lib.rs" "Item"}`
+const CODE_DIRECTIVE: &str = "{code ";
+
+/// The start of a directive inlining another template, as in `{include "intro.md"}`
+const INCLUDE_DIRECTIVE: &str = "{include ";
+
+/// A template, along with the templates it includes
+pub struct IncludedTemplates {
+    /// The template, where every `{include "path"}` directive is replaced by the included template
+    pub template: String,
+    /// The included templates, along with their content
+    pub files: Vec<(PathBuf, String)>,
+    /// The including template, along with its content
+    root: (PathBuf, String),
+    /// Where the parts of `template` come from, in order, as
+    /// (offset in `template`, index in `files` or `None` for the root, offset in that file)
+    parts: Vec<(usize, Option<usize>, usize)>,
+}
+
+impl IncludedTemplates {
+    /// The template file a span of `template` comes from, and the span within that file
+    pub fn source(&self, (offset, len): (usize, usize)) -> (NamedSource<String>, (usize, usize)) {
+        let part = self.parts.partition_point(|(start, ..)| *start <= offset);
+        let (start, file, file_offset) = self
+            .parts
+            .get(part.saturating_sub(1))
+            .copied()
+            .unwrap_or_default();
+        let (path, content) = file.map_or(&self.root, |file| &self.files[file]);
+        let offset = (file_offset + offset - start).min(content.len());
+        (
+            NamedSource::new(path.to_string_lossy(), content.clone()),
+            (offset, len.min(content.len() - offset)),
+        )
+    }
+}
+
+/// The offset of a line (1-based) and column (in bytes) of a template, as tinytemplate reports them
+pub fn offset_at(template: &str, line: usize, column: usize) -> usize {
+    let line_start: usize = template
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    (line_start + column).min(template.len())
+}
+
+/// Inlines the templates included with `{include "path"}` directives, recursively,
+/// where paths are relative to the including template
+pub fn include_templates(template: &str, template_path: &Path) -> Result<IncludedTemplates> {
+    let mut included = IncludedTemplates {
+        template: String::new(),
+        files: vec![],
+        root: (template_path.to_path_buf(), template.to_string()),
+        parts: vec![],
+    };
+    let mut stack =
+        vec![fs::canonicalize(template_path).unwrap_or_else(|_| template_path.to_path_buf())];
+    inline_includes(template, template_path, None, &mut stack, &mut included)?;
+    Ok(included)
+}
+
+/// Inlines the templates included by a template (`file` in `included.files`, or the root),
+/// given the templates (`stack`) that led to including it
+fn inline_includes(
+    template: &str,
+    template_path: &Path,
+    file: Option<usize>,
+    stack: &mut Vec<PathBuf>,
+    included: &mut IncludedTemplates,
+) -> Result<()> {
+    let src = || NamedSource::new(template_path.to_string_lossy(), template.to_string());
+
+    let mut rest = template;
+    while let Some(start) = rest.find(INCLUDE_DIRECTIVE) {
+        let offset = template.len() - rest.len() + start;
+        let directive = quoted(&rest[start + INCLUDE_DIRECTIVE.len()..])
+            .and_then(|(path, after)| Some((path, after.trim_start().strip_prefix('}')?)));
+        let Some((path, after)) = directive else {
+            let len = rest[start..]
+                .find('}')
+                .map_or(INCLUDE_DIRECTIVE.len(), |end| end + 1);
+            return Err(SpecError::BadIncludeDirective {
+                _src: src(),
+                _bad_bit: (offset, len),
+            }
+            .into());
+        };
+        let path_bit = (
+            path.as_ptr() as usize - template.as_ptr() as usize,
+            path.len(),
+        );
+
+        let include = template_path.parent().unwrap_or(Path::new("")).join(path);
+        let Ok(include) = fs::canonicalize(&include) else {
+            return Err(SpecError::MissingInclude {
+                _src: src(),
+                _bad_bit: path_bit,
+            }
+            .into());
+        };
+        if let Some(position) = stack.iter().position(|included| *included == include) {
+            let cycle: Vec<_> = stack[position..]
+                .iter()
+                .chain([&include])
+                .map(|file| file.display().to_string())
+                .collect();
+            return Err(SpecError::IncludeCycle {
+                _src: src(),
+                _bad_bit: path_bit,
+                cycle: cycle.join(" -> "),
+            }
+            .into());
+        }
+
+        let content = fs::read_to_string(&include)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("could not read template {}", include.display()))?;
+
+        push_part(included, &rest[..start], file, offset - start);
+        included.files.push((include.clone(), content.clone()));
+        let index = included.files.len() - 1;
+        stack.push(include.clone());
+        inline_includes(&content, &include, Some(index), stack, included)?;
+        stack.pop();
+
+        rest = after;
+    }
+    push_part(included, rest, file, template.len() - rest.len());
+
+    Ok(())
+}
+
+/// Appends some text of a template (`file` in `included.files`, or the root),
+/// found at `offset` in that template
+fn push_part(included: &mut IncludedTemplates, text: &str, file: Option<usize>, offset: usize) {
+    if !text.is_empty() {
+        included.parts.push((included.template.len(), file, offset));
+        included.template.push_str(text);
+    }
+}
+
+/// Lists the sections used by a template (as in `{sections.name}` or `{{ if sections.name }}`),
+/// along with the offset of their names
+pub fn placeholders(template: &str) -> Vec<(&str, usize)> {
+    let mut placeholders = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + len];
+        let tag_offset = template.len() - rest.len() + start + 1;
+        rest = &rest[start + len + 1..];
+
+        for (index, _) in tag.match_indices("sections.") {
+            // `sections` must be the start of a path (possibly `@root.sections`)
+            let before = &tag[..index];
+            let path_start = before.is_empty()
+                || before.ends_with(|c: char| c.is_whitespace() || c == '{' || c == '-')
+                || before.ends_with("@root.");
+            if !path_start {
+                continue;
+            }
+
+            let name_start = index + "sections.".len();
+            let name = tag[name_start..]
+                .split(|c: char| c.is_whitespace() || c == '.' || c == '|')
+                .next()
+                .unwrap_or_default()
+                .trim_end_matches('-');
+            if !name.is_empty() {
+                placeholders.push((name, tag_offset + name_start));
+            }
+        }
+    }
+    placeholders
+}
+
+/// Code embedded in a template
+pub struct EmbeddedCode {
+    /// The template, where every `{code "path" "Item"}` directive is replaced by a `{code.N}` value
+    /// (padded to the length of the directive, so that offsets in both templates match)
+    pub template: String,
+    /// The fenced code blocks to render in place of the directives
+    pub code: Vec<String>,
+    /// The files the code was embedded from
+    pub files: Vec<PathBuf>,
+}
+
+/// Embeds the definition of the Rust items referenced by `{code "path" "Item"}` directives,
+/// where `resolve` turns the path of a directive into the path of the file
+pub fn embed_code(
+    included: &IncludedTemplates,
+    languages: &HashMap<String, Language>,
+    resolve: impl Fn(&str) -> Result<PathBuf>,
+) -> Result<EmbeddedCode> {
+    let template = included.template.as_str();
+    let error_at = |bit, error: fn(NamedSource<String>, (usize, usize)) -> SpecError| {
+        let (src, bit) = included.source(bit);
+        Err(error(src, bit).into())
+    };
+
+    let mut result = EmbeddedCode {
+        template: String::new(),
+        code: vec![],
+        files: vec![],
+    };
+    let mut rest = template;
+    while let Some(start) = rest.find(CODE_DIRECTIVE) {
+        let offset = template.len() - rest.len() + start;
+        let Some(directive) = parse_directive(&rest[start..]) else {
+            let len = rest[start..]
+                .find('}')
+                .map_or(CODE_DIRECTIVE.len(), |end| end + 1);
+            return error_at((offset, len), |_src, _bad_bit| {
+                SpecError::BadCodeDirective { _src, _bad_bit }
+            });
+        };
+        let path_bit = (offset + directive.path_offset, directive.path.len());
+        let item_bit = (offset + directive.item_offset, directive.item.len());
+
+        let file = resolve(directive.path)?;
+        let extension = file.extension().and_then(|ext| ext.to_str());
+        if extension != Some("rs") {
+            return error_at(path_bit, |_src, _bad_bit| SpecError::CodeNotInRust {
+                _src,
+                _bad_bit,
+            });
+        }
+
+        let source = fs::read_to_string(&file)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("could not read file {}", file.display()))?;
+        let items = rust_parser::parse_items(&source).map_err(|e| SpecError::CantParseRust {
+            _src: NamedSource::new(file.to_string_lossy(), source.to_string()),
+            _bad_bit: (rust_parser::offset(&source, e.span().start()), 0),
+        })?;
+        // the item can also be a path within the file (as in `Type::method`)
+        let item = match rust_parser::find_item(&items, directive.item) {
+            Ok(item) => item,
+            Err(candidates) if candidates.is_empty() => {
+                return error_at(item_bit, |_src, _bad_bit| SpecError::MissingCodeItem {
+                    _src,
+                    _bad_bit,
+                });
+            }
+            Err(candidates) => {
+                let candidates: Vec<_> = candidates
+                    .iter()
+                    .map(|item| format!("`{}` (line {})", item.path(), item.line))
+                    .collect();
+                let (_src, _bad_bit) = included.source(item_bit);
+                return Err(SpecError::AmbiguousCodeItem {
+                    _src,
+                    _bad_bit,
+                    candidates: candidates.join(", "),
+                }
+                .into());
+            }
+        };
+
+        let lang = languages
+            .get("rs")
+            .and_then(|language| language.code_fence.as_deref())
+            .unwrap_or("rs");
+        result.template.push_str(&rest[..start]);
+        // directives are always longer than the values replacing them
+        let value = format!("{{code.{}", result.code.len());
+        result.template.push_str(&format!(
+            "{value:<width$}}}",
+            width = directive.len.saturating_sub(1)
+        ));
+        result.code.push(format!("```{lang}\n{}\n```", item.source));
+        result.files.push(file);
+
+        rest = &rest[start + directive.len..];
+    }
+    result.template.push_str(rest);
+
+    Ok(result)
+}
+
+/// A parsed `{code "path" "Item"}` directive
+#[derive(Debug, PartialEq, Eq)]
+struct CodeDirective<'a> {
+    /// The path of the file
+    path: &'a str,
+    /// The offset of the path within the directive
+    path_offset: usize,
+    /// The name of the item
+    item: &'a str,
+    /// The offset of the name of the item within the directive
+    item_offset: usize,
+    /// The length of the directive, including its braces
+    len: usize,
+}
+
+/// Parses the directive at the start of `text`
+fn parse_directive(text: &str) -> Option<CodeDirective<'_>> {
+    let rest = text.strip_prefix(CODE_DIRECTIVE)?;
+    let (path, rest) = quoted(rest)?;
+    let (item, rest) = quoted(rest)?;
+    let rest = rest.trim_start().strip_prefix('}')?;
+
+    // all the parts are slices of `text`
+    let offset_of = |part: &str| part.as_ptr() as usize - text.as_ptr() as usize;
+    Some(CodeDirective {
+        path,
+        path_offset: offset_of(path),
+        item,
+        item_offset: offset_of(item),
+        len: text.len() - rest.len(),
+    })
+}
+
+/// Parses a string between double quotes, after optional whitespaces,
+/// and returns it along with the rest of the text
+fn quoted(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start().strip_prefix('"')?;
+    let end = text.find('"')?;
+    Some((&text[..end], &text[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_directive() {
+        let directive = parse_directive(r#"{code "src/lib.rs"  "Item" } and more"#).unwrap();
+        assert_eq!(
+            directive,
+            CodeDirective {
+                path: "src/lib.rs",
+                path_offset: 7,
+                item: "Item",
+                item_offset: 21,
+                len: 28,
+            }
+        );
+    }
+
+    #[test]
+    fn test_include_templates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(dir.join("parts")).unwrap();
+        fs::write(dir.join("parts/a.md"), r#"A {include "b.md"}"#).unwrap();
+        fs::write(dir.join("parts/b.md"), "B").unwrap();
+
+        let template = r#"{include "parts/a.md"}, {include "parts/b.md"}"#;
+        let included = include_templates(template, &dir.join("template.md")).unwrap();
+        assert_eq!(included.template, "A B, B");
+        let source = |bit| {
+            let (src, bit) = included.source(bit);
+            (src.name().to_string(), bit)
+        };
+        let root = dir.join("template.md").display().to_string();
+        let b = dir.join("parts/b.md").display().to_string();
+        assert_eq!(source((2, 1)), (b.clone(), (0, 1)));
+        assert_eq!(source((3, 2)), (root, (22, 2)));
+        assert_eq!(source((5, 1)), (b, (0, 1)));
+        let files: Vec<_> = included.files.into_iter().map(|(file, _)| file).collect();
+        assert_eq!(
+            files,
+            vec![
+                dir.join("parts/a.md"),
+                dir.join("parts/b.md"),
+                dir.join("parts/b.md")
+            ]
+        );
+
+        fs::write(dir.join("parts/b.md"), r#"{include "a.md"}"#).unwrap();
+        assert!(include_templates(template, &dir.join("template.md")).is_err());
+    }
+
+    #[test]
+    fn test_embed_code_in_included_template() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        fs::write(dir.join("lib.rs"), "pub struct Foo;\n").unwrap();
+        fs::write(dir.join("code.md"), "Foo:\n{code \"lib.rs\"\n  \"Foo\"}\n").unwrap();
+
+        let template = "# Code\n\n{include \"code.md\"}{code \"lib.rs\" \"Foo\"}";
+        let included = include_templates(template, &dir.join("template.md")).unwrap();
+        let embedded = embed_code(&included, &HashMap::new(), |path| Ok(dir.join(path))).unwrap();
+        assert_eq!(embedded.template.len(), included.template.len());
+        assert_eq!(
+            embedded.template,
+            "# Code\n\nFoo:\n{code.0               }\n{code.1             }"
+        );
+
+        fs::write(dir.join("code.md"), "Foo:\n{code \"lib.rs\" \"Bar\"}\n").unwrap();
+        let included = include_templates(template, &dir.join("template.md")).unwrap();
+        let error = embed_code(&included, &HashMap::new(), |path| Ok(dir.join(path)))
+            .err()
+            .unwrap();
+        let Some(SpecError::MissingCodeItem { _src, _bad_bit }) = error.downcast_ref() else {
+            panic!("unexpected error: {error:?}");
+        };
+        assert_eq!(_src.name(), dir.join("code.md").display().to_string());
+        assert_eq!(*_bad_bit, (21, 3));
+    }
+
+    #[test]
+    fn test_offset_at() {
+        assert_eq!(offset_at("ab\ncd\nef", 1, 1), 1);
+        assert_eq!(offset_at("ab\ncd\nef", 3, 0), 6);
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let template =
+            "{sections.a}\n{{ if sections.b }}{ sections.c | indent 2 -}{{ endif }}{@root.sections.d}{subsections.e}";
+        assert_eq!(
+            placeholders(template),
+            vec![("a", 10), ("b", 28), ("c", 43), ("d", 85)]
+        );
+    }
+
+    #[test]
+    fn test_parse_bad_directives() {
+        assert_eq!(parse_directive(r#"{code "src/lib.rs"}"#), None);
+        assert_eq!(parse_directive(r#"{code src/lib.rs Item}"#), None);
+        assert_eq!(parse_directive(r#"{code "src/lib.rs" "Item""#), None);
+    }
+}
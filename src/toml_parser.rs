@@ -1,11 +1,11 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
 use std::{collections::HashMap, fs::File, io::Read, path::Path};
 
 use miette::{IntoDiagnostic, Result, WrapErr};
 
-//~ spec:startcode
+//~ spec:item
 /// A specification file contains a specification, as well as sections of (title, text)
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Specification {
     /// information about a specification
     pub metadata: Metadata,
@@ -16,24 +16,48 @@ pub struct Specification {
     pub output: Option<OutputConfig>,
     /// files to use for the specification's content
     pub sections: HashMap<String, String>,
+    /// comment syntax of file extensions, overriding the built-in ones
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub languages: HashMap<String, Language>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+//~ spec:item
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     /// main template file
     pub template: String,
+    /// link every extracted block to its source: `github`, `gitlab`,
+    /// or a URL pattern using `{commit}`, `{path}` and `{line}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_links: Option<String>,
 }
 
+//~ spec:item
 /// Output configuration for the specification
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct OutputConfig {
     /// The markdown flavor to use (mdbook or docusaurus)
     #[serde(default)]
     pub flavor: Option<String>,
 }
 
+//~ spec:item
+/// How spec comments are written in files with a given extension
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Language {
+    /// The delimiter starting a spec comment (e.g. `--~`)
+    pub start: String,
+    /// The delimiter ending a spec comment, for block comments (e.g. `-}`)
+    #[serde(default)]
+    pub end: Option<String>,
+    /// The language of extracted code blocks (defaults to the extension)
+    #[serde(default)]
+    pub code_fence: Option<String>,
+}
+
+//~ spec:item
 /// Metadata about a specification
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Metadata {
     /// Name of the specification
     pub name: String,
@@ -44,10 +68,109 @@ pub struct Metadata {
     /// Authors, if any
     pub authors: Vec<String>,
 }
-//~ spec:endcode
+
+//~ spec:item
+/// A specification listed in a specification file with `[[spec]]`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpecEntry {
+    /// where to write the specification, relative to the specification file
+    /// (defaults to the name of the specification)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_file: Option<String>,
+    /// the specification itself
+    #[serde(flatten)]
+    pub specification: Specification,
+}
+
+/// A specification file listing several specifications
+#[derive(Deserialize, Debug)]
+struct Specifications {
+    /// output configuration shared by all the specifications
+    #[serde(default)]
+    output: Option<OutputConfig>,
+    spec: Vec<SpecEntry>,
+}
+
+/// The content of a specification file
+#[derive(Debug)]
+pub enum Manifest {
+    /// A single specification
+    Single(Box<Specification>),
+    /// Several specifications, listed with `[[spec]]`,
+    /// along with the output configuration they share
+    Multiple(Vec<SpecEntry>, Option<OutputConfig>),
+}
+
+impl Manifest {
+    /// The output configuration of the specification file
+    pub fn output(&self) -> Option<&OutputConfig> {
+        match self {
+            Manifest::Single(specification) => specification.output.as_ref(),
+            Manifest::Multiple(_, output) => output.as_ref(),
+        }
+    }
+}
+
+/// The names of the sections of a specification, along with where they are defined
+#[derive(Deserialize, Default)]
+struct SectionKeys {
+    #[serde(default)]
+    sections: HashMap<toml::Spanned<String>, IgnoredAny>,
+}
+
+/// The section names of the specifications listed with `[[spec]]`
+#[derive(Deserialize)]
+struct SpecsSectionKeys {
+    spec: Vec<SectionKeys>,
+}
+
+/// Finds where the sections of each specification are defined in a specification file,
+/// as the (offset, length) of their names
+pub fn section_spans(content: &str) -> Vec<HashMap<String, (usize, usize)>> {
+    let specs = match toml::from_str::<SpecsSectionKeys>(content) {
+        Ok(specs) => specs.spec,
+        Err(_) => vec![toml::from_str(content).unwrap_or_default()],
+    };
+
+    specs
+        .into_iter()
+        .map(|keys| {
+            keys.sections
+                .into_keys()
+                .map(|name| {
+                    let span = name.span();
+                    (name.into_inner(), (span.start, span.len()))
+                })
+                .collect()
+        })
+        .collect()
+}
 
 /// Parse a `Specification.toml` file into a [Specification] struct.
 pub fn parse_toml_spec(spec_file: &Path) -> Result<Specification> {
+    toml::from_str(&read_spec_file(spec_file)?).into_diagnostic()
+}
+
+/// Parse a `Specification.toml` file that can list several specifications with `[[spec]]`.
+pub fn parse_manifest(spec_file: &Path) -> Result<Manifest> {
+    let content = read_spec_file(spec_file)?;
+
+    let table: toml::Table = toml::from_str(&content).into_diagnostic()?;
+    if !table.contains_key("spec") {
+        return toml::from_str(&content)
+            .into_diagnostic()
+            .map(|specification| Manifest::Single(Box::new(specification)));
+    }
+
+    let specifications: Specifications = toml::from_str(&content).into_diagnostic()?;
+    Ok(Manifest::Multiple(
+        specifications.spec,
+        specifications.output,
+    ))
+}
+
+/// Reads a `Specification.toml` file
+pub fn read_spec_file(spec_file: &Path) -> Result<String> {
     let mut file = File::open(spec_file).into_diagnostic().wrap_err_with(|| format!("cannot open the specification file {}, make sure you pass a specification toml file via --specification-path", spec_file.display()))?;
 
     let mut content = String::new();
@@ -60,5 +183,5 @@ pub fn parse_toml_spec(spec_file: &Path) -> Result<Specification> {
             )
         })?;
 
-    toml::from_str(&content).into_diagnostic()
+    Ok(content)
 }
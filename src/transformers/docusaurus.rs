@@ -20,25 +20,28 @@ impl MarkdownTransformer for DocusaurusTransformer {
 /// ```` ```admonish warning ```` -> `:::warning`
 /// ```` ```admonish ```` -> `:::note` (default type)
 /// Closing ``` -> `:::`
+/// (the admonition can use a longer fence to contain code blocks,
+/// it's then only closed by the same fence)
 fn transform_admonitions(content: &str) -> String {
     // Match ```admonish with optional type (defaults to "note")
-    let re = Regex::new(r"^```admonish(?:\s+(\w+))?(?:\s.*)?$").unwrap();
+    let re = Regex::new(r"^(`{3,})admonish(?:\s+(\w+))?(?:\s.*)?$").unwrap();
     let mut result = String::new();
-    let mut in_admonition = false;
-    let mut admonition_indent = String::new();
+    let mut admonition: Option<(String, String)> = None;
 
     for line in content.lines() {
         let trimmed = line.trim_start();
         let current_indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
 
-        if let Some(caps) = re.captures(trimmed) {
-            let admonition_type = caps.get(1).map_or("note", |m| m.as_str());
-            in_admonition = true;
-            admonition_indent = current_indent.clone();
-            result.push_str(&format!("{}:::{}\n", current_indent, admonition_type));
-        } else if in_admonition && trimmed == "```" && current_indent == admonition_indent {
-            in_admonition = false;
+        let closes = admonition
+            .as_ref()
+            .is_some_and(|(fence, indent)| trimmed == fence && current_indent == *indent);
+        if closes {
+            admonition = None;
             result.push_str(&format!("{}:::\n", current_indent));
+        } else if let Some(caps) = re.captures(trimmed).filter(|_| admonition.is_none()) {
+            let admonition_type = caps.get(2).map_or("note", |m| m.as_str());
+            admonition = Some((caps[1].to_string(), current_indent.clone()));
+            result.push_str(&format!("{}:::{}\n", current_indent, admonition_type));
         } else {
             result.push_str(line);
             result.push('\n');
@@ -194,6 +197,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_transform_admonitions_with_code_block() {
+        let input = "````admonish warning\nText\n\n```rust\nlet x = 1;\n```\n````\n";
+        let expected = ":::warning\nText\n\n```rust\nlet x = 1;\n```\n:::\n";
+        assert_eq!(transform_admonitions(input), expected);
+    }
+
     #[test]
     fn test_transform_admonitions_with_surrounding_content() {
         let input = "Before\n\n```admonish warning\nWarning text\n```\n\nAfter";
@@ -0,0 +1,166 @@
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    build::OutputFormat,
+    errors::SpecError,
+    formats,
+    init::DEFAULT_MANIFEST,
+    toml_parser::{self, Manifest},
+};
+
+/// The parts of a `Cargo.toml` file we care about
+#[derive(Deserialize)]
+struct CargoToml {
+    package: Option<Package>,
+    workspace: Option<Workspace>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Workspace {
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+/// A crate of the workspace that has a specification
+#[derive(Debug, PartialEq, Eq)]
+pub struct Member {
+    /// The name of the crate (or of its directory, if it isn't a package)
+    pub name: String,
+    /// The directory of the crate
+    pub dir: PathBuf,
+}
+
+impl Member {
+    /// The specification file of the crate
+    pub fn specification(&self) -> PathBuf {
+        self.dir.join(DEFAULT_MANIFEST)
+    }
+
+    /// The file the specification of the crate is written to,
+    /// relative to the directory of the crate
+    /// (a specification file listing several specifications has its own output files)
+    pub fn output_file(
+        &self,
+        output_file: Option<PathBuf>,
+        output_format: OutputFormat,
+    ) -> Result<Option<PathBuf>> {
+        match toml_parser::parse_manifest(&self.specification())? {
+            Manifest::Single(_) => Ok(Some(
+                self.dir
+                    .join(output_file.unwrap_or_else(|| output_format.default_output_file())),
+            )),
+            Manifest::Multiple(..) => Ok(output_file),
+        }
+    }
+}
+
+/// Lists the members of the workspace containing `dir` that have a specification,
+/// by reading the `members` (and `exclude`) of the workspace's `Cargo.toml`
+pub fn members(dir: &Path) -> Result<Vec<Member>> {
+    // the workspace is the closest `Cargo.toml` with a `[workspace]` table
+    let (root, cargo_toml) = dir
+        .ancestors()
+        .filter_map(|dir| Some((dir, read_cargo_toml(dir)?)))
+        .find(|(_, cargo_toml)| cargo_toml.workspace.is_some())
+        .ok_or_else(|| SpecError::NoWorkspace(dir.to_path_buf()))?;
+    let workspace = cargo_toml.workspace.as_ref().unwrap();
+
+    // its members can be glob patterns, and the root package is a member too
+    let excluded = expand(root, &workspace.exclude)?;
+    let mut dirs = expand(root, &workspace.members)?;
+    dirs.retain(|dir| !excluded.contains(dir));
+    if cargo_toml.package.is_some() {
+        dirs.push(root.to_path_buf());
+    }
+    dirs.sort();
+    dirs.dedup();
+
+    // only the members with a `Specification.toml` are built
+    let members: Vec<_> = dirs
+        .into_iter()
+        .filter(|dir| dir.join(DEFAULT_MANIFEST).is_file())
+        .map(|dir| {
+            let name = read_cargo_toml(&dir)
+                .and_then(|cargo_toml| cargo_toml.package)
+                .map(|package| package.name)
+                .unwrap_or_else(|| dir.file_name().unwrap().to_string_lossy().to_string());
+            Member { name, dir }
+        })
+        .collect();
+
+    if members.is_empty() {
+        return Err(SpecError::NoWorkspaceSpecs(root.to_path_buf()).into());
+    }
+
+    Ok(members)
+}
+
+/// Fails if the output file is stdout,
+/// as the specifications of all the members would be mixed together
+pub fn reject_stdout(command: &'static str, output_file: Option<&Path>) -> Result<()> {
+    if output_file == Some(Path::new(formats::STDOUT)) {
+        return Err(SpecError::StdoutOutputFile(command).into());
+    }
+
+    Ok(())
+}
+
+/// Runs `f` on every member, reporting the result for each of them,
+/// and fails if any of them failed
+pub fn for_each_member(members: &[Member], mut f: impl FnMut(&Member) -> Result<()>) -> Result<()> {
+    let mut failed = vec![];
+    for member in members {
+        eprintln!("\n=> {} ({})", member.name, member.dir.display());
+        match f(member) {
+            Ok(()) => eprintln!("=> {}: ok", member.name),
+            Err(e) => {
+                eprintln!("{e:?}");
+                eprintln!("=> {}: failed", member.name);
+                failed.push(member.name.clone());
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(SpecError::FailedMembers(failed).into());
+    }
+
+    Ok(())
+}
+
+/// Parses the `Cargo.toml` of a directory, if there is one
+fn read_cargo_toml(dir: &Path) -> Option<CargoToml> {
+    let content = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    toml::from_str(&content).ok()
+}
+
+/// Lists the directories matching the given paths (or glob patterns) of a workspace
+fn expand(root: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut dirs = vec![];
+    for pattern in patterns {
+        let path = root.join(pattern);
+        let entries =
+            glob::glob(&path.to_string_lossy()).map_err(|_| SpecError::BadGlob(pattern.clone()))?;
+        for entry in entries {
+            let entry = entry
+                .into_diagnostic()
+                .wrap_err_with(|| format!("could not list workspace member {pattern}"))?;
+            if entry.is_dir() {
+                dirs.push(entry);
+            }
+        }
+    }
+    Ok(dirs)
+}
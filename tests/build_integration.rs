@@ -0,0 +1,1643 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+use tempfile::TempDir;
+
+fn cargo_spec_binary() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("target/debug/cargo-spec");
+    path
+}
+
+fn setup_spec(dir: &Path, config: &str, sections: &str, template: &str) -> PathBuf {
+    let spec_path = dir.join("Specification.toml");
+
+    fs::write(
+        &spec_path,
+        format!(
+            r#"[metadata]
+name = "Test"
+authors = ["Test Author"]
+
+[config]
+template = "template.md"
+{config}
+
+[sections]
+{sections}
+"#
+        ),
+    )
+    .unwrap();
+
+    fs::write(dir.join("template.md"), template).unwrap();
+    spec_path
+}
+
+fn run_cargo_spec(command: &str, spec_path: &Path) -> std::process::Output {
+    Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg(command)
+        .arg("-s")
+        .arg(spec_path)
+        .arg("-o")
+        .arg(spec_path.with_file_name("output.md"))
+        .output()
+        .expect("Failed to execute cargo-spec")
+}
+
+fn build_spec(spec_path: &Path) -> String {
+    let output = run_cargo_spec("build", spec_path);
+
+    if !output.status.success() {
+        panic!(
+            "cargo-spec failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    fs::read_to_string(spec_path.with_file_name("output.md")).expect("Failed to read output file")
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .status()
+        .expect("Failed to execute git");
+    assert!(status.success(), "git {args:?} failed");
+}
+
+// =============================================================================
+// Source Links Tests
+// =============================================================================
+
+#[test]
+fn test_source_links_github() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        r#"source_links = "github""#,
+        r#"code = "src/lib.rs""#,
+        "{sections.code}",
+    );
+    fs::create_dir(dir.path().join("src")).unwrap();
+    fs::write(
+        dir.path().join("src/lib.rs"),
+        "//~ first block\nfn a() {}\n\n//~ second block\n",
+    )
+    .unwrap();
+
+    git(dir.path(), &["init", "-q"]);
+    git(
+        dir.path(),
+        &["remote", "add", "origin", "git@github.com:owner/repo.git"],
+    );
+    git(dir.path(), &["add", "-A"]);
+    git(
+        dir.path(),
+        &[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "init",
+        ],
+    );
+
+    let result = build_spec(&spec_path);
+
+    assert!(result.contains("first block"));
+    assert!(result.contains("https://github.com/owner/repo/blob/"));
+    assert!(result.contains("/src/lib.rs#L1)"));
+    assert!(result.contains("/src/lib.rs#L4)"));
+}
+
+#[test]
+fn test_source_links_typo_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        r#"source_links = "githb""#,
+        r#"code = "lib.rs""#,
+        "{sections.code}",
+    );
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`githb` is not a valid `source_links` value"));
+    assert!(stderr.contains("`github`, `gitlab`"));
+}
+
+#[test]
+fn test_no_source_links_by_default() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"code = "lib.rs""#, "{sections.code}");
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(result, "some spec\n");
+}
+
+// =============================================================================
+// Glob and Directory Sections Tests
+// =============================================================================
+
+#[test]
+fn test_section_from_glob() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"protocol = "src/**/*.rs""#,
+        "{sections.protocol}",
+    );
+    fs::create_dir_all(dir.path().join("src/protocol")).unwrap();
+    fs::write(dir.path().join("src/b.rs"), "//~ from b\n").unwrap();
+    fs::write(dir.path().join("src/protocol/a.rs"), "//~ from a\n").unwrap();
+    fs::write(dir.path().join("src/empty.rs"), "fn main() {}\n").unwrap();
+    fs::write(dir.path().join("src/notes.md"), "not matched\n").unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(
+        result,
+        "**`src/b.rs`**\n\nfrom b\n\n**`src/protocol/a.rs`**\n\nfrom a\n\n"
+    );
+}
+
+#[test]
+fn test_section_from_directory() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"protocol = "protocol""#,
+        "{sections.protocol}",
+    );
+    fs::create_dir_all(dir.path().join("protocol/nested")).unwrap();
+    fs::write(dir.path().join("protocol/z.py"), "#~ from z\n").unwrap();
+    fs::write(dir.path().join("protocol/nested/y.rs"), "//~ from y\n").unwrap();
+
+    let result = build_spec(&spec_path);
+
+    let y = result.find("from y").unwrap();
+    let z = result.find("from z").unwrap();
+    assert!(y < z);
+    assert!(result.contains("**`protocol/nested/y.rs`**"));
+}
+
+#[test]
+fn test_section_from_directory_skips_unsupported_files() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "[languages.ml4]\nstart = \"(*~\"\nend = \"*)\"",
+        r#"protocol = "protocol""#,
+        "{sections.protocol}",
+    );
+    fs::create_dir_all(dir.path().join("protocol")).unwrap();
+    fs::write(dir.path().join("protocol/a.rs"), "//~ from a\n").unwrap();
+    fs::write(dir.path().join("protocol/b.ml4"), "(*~ from b *)\n").unwrap();
+    fs::write(
+        dir.path().join("protocol/Makefile"),
+        "all:\n\t//~ not spec\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("protocol/LICENSE"), "MIT\n").unwrap();
+    fs::write(
+        dir.path().join("protocol/logo.png"),
+        [0x89, b'P', b'N', b'G', 0xff, 0xfe],
+    )
+    .unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(
+        result,
+        "**`protocol/a.rs`**\n\nfrom a\n\n**`protocol/b.ml4`**\n\nfrom b\n\n"
+    );
+}
+
+#[test]
+fn test_section_glob_without_matches_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"protocol = "src/*.rs""#,
+        "{sections.protocol}",
+    );
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No files match"));
+}
+
+// =============================================================================
+// Check Tests
+// =============================================================================
+
+#[test]
+fn test_check_up_to_date() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"code = "lib.rs""#, "{sections.code}");
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+    build_spec(&spec_path);
+
+    let output = run_cargo_spec("check", &spec_path);
+
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_check_outdated() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"code = "lib.rs""#, "{sections.code}");
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+    build_spec(&spec_path);
+    fs::write(dir.path().join("lib.rs"), "//~ some other spec\n").unwrap();
+
+    let output = run_cargo_spec("check", &spec_path);
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-some spec"));
+    assert!(stdout.contains("+some other spec"));
+}
+
+#[test]
+fn test_check_with_source_links() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        r#"source_links = "https://example.com/{commit}/{path}#L{line}""#,
+        r#"code = "lib.rs""#,
+        "{sections.code}",
+    );
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+    let commit = |message: &str| {
+        git(dir.path(), &["add", "-A"]);
+        git(
+            dir.path(),
+            &[
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "commit",
+                "-q",
+                "-m",
+                message,
+            ],
+        );
+    };
+    git(dir.path(), &["init", "-q"]);
+    commit("init");
+
+    // committing the rebuilt specification moves the commit its links are pinned to
+    let result = build_spec(&spec_path);
+    assert!(result.contains("[source](https://example.com/"));
+    commit("rebuild the specification");
+    assert!(run_cargo_spec("check", &spec_path).status.success());
+
+    fs::write(dir.path().join("lib.rs"), "\n//~ some spec\n").unwrap();
+    commit("move the spec comment");
+    let output = run_cargo_spec("check", &spec_path);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("/lib.rs#L2)"));
+}
+
+#[test]
+fn test_check_with_source_links_catches_other_hashes() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        r#"source_links = "https://example.com/{commit}/{path}#L{line}""#,
+        r#"code = "lib.rs""#,
+        "{sections.code}",
+    );
+    let vector = |digest: &str| format!("//~ sha1(\"abc\") = {digest}\n");
+    fs::write(
+        dir.path().join("lib.rs"),
+        vector("a9993e364706816aba3e25717850c26c9cd0d89d"),
+    )
+    .unwrap();
+    git(dir.path(), &["init", "-q"]);
+    git(dir.path(), &["add", "-A"]);
+    git(
+        dir.path(),
+        &[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-q",
+            "-m",
+            "init",
+        ],
+    );
+    build_spec(&spec_path);
+    assert!(run_cargo_spec("check", &spec_path).status.success());
+
+    // a 40 hex digits hash outside of the source links is part of the specification
+    fs::write(
+        dir.path().join("lib.rs"),
+        vector("0000000000000000000000000000000000000000"),
+    )
+    .unwrap();
+    let output = run_cargo_spec("check", &spec_path);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_check_missing_output() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"code = "lib.rs""#, "{sections.code}");
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+
+    let output = run_cargo_spec("check", &spec_path);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_build_to_missing_directory_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", "", "# Spec\n");
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(dir.path().join("missing/output.md"))
+        .output()
+        .expect("Failed to execute cargo-spec");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("doesn't exist"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_build_to_stdout() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"code = "lib.rs""#, "{sections.code}");
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg("-")
+        .current_dir(dir.path())
+        .output()
+        .expect("Failed to execute cargo-spec");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "some spec\n");
+    assert!(!dir.path().join("-").exists());
+}
+
+#[test]
+fn test_check_and_watch_to_stdout_fail() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", "", "# Spec\n");
+    fs::write(dir.path().join("-"), "# Spec\n").unwrap();
+
+    for command in ["check", "watch"] {
+        let output = Command::new(cargo_spec_binary())
+            .arg("spec")
+            .arg(command)
+            .arg("-s")
+            .arg(&spec_path)
+            .arg("-o")
+            .arg("-")
+            .current_dir(dir.path())
+            .output()
+            .expect("Failed to execute cargo-spec");
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+            "`cargo spec {command}` can't use stdout as its output file"
+        )));
+    }
+}
+
+#[test]
+fn test_build_book_to_stdout_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", "", "# Spec\n");
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg("-")
+        .arg("--output-format")
+        .arg("book")
+        .current_dir(dir.path())
+        .output()
+        .expect("Failed to execute cargo-spec");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't be written to a stream"));
+}
+
+// =============================================================================
+// Named Anchors Tests
+// =============================================================================
+
+const ANCHORED_SOURCE: &str = r#"//~ intro
+//~ spec:begin(handshake)
+//~ the handshake
+fn handshake() {}
+//~ spec:startcode
+struct Hello;
+//~ spec:endcode
+//~ spec:end(handshake)
+//~ spec:begin(transport)
+//~ the transport
+//~ spec:end(transport)
+"#;
+
+#[test]
+fn test_anchor_sections() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "handshake = \"lib.rs#handshake\"\ntransport = \"lib.rs#transport\"\nall = \"lib.rs\"",
+        "A:{sections.handshake}B:{sections.transport}C:{sections.all}",
+    );
+    fs::write(dir.path().join("lib.rs"), ANCHORED_SOURCE).unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(
+        result,
+        "A:the handshake\n```rs\nstruct Hello;\n```\nB:the transport\nC:intro\nthe handshake\n```rs\nstruct Hello;\n```\nthe transport\n"
+    );
+}
+
+#[test]
+fn test_missing_anchor_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"handshake = "lib.rs#nope""#,
+        "{sections.handshake}",
+    );
+    fs::write(dir.path().join("lib.rs"), ANCHORED_SOURCE).unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No block named `nope`"));
+}
+
+#[test]
+fn test_unterminated_anchor_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"all = "lib.rs""#, "{sections.all}");
+    fs::write(dir.path().join("lib.rs"), "//~ spec:begin(a)\n//~ text\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+}
+
+// =============================================================================
+// Languages Tests
+// =============================================================================
+
+#[test]
+fn test_custom_language() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = dir.path().join("Specification.toml");
+    fs::write(
+        &spec_path,
+        r#"[metadata]
+name = "Test"
+authors = []
+
+[config]
+template = "template.md"
+
+[sections]
+migration = "migration.sql"
+
+[languages.sql]
+start = "--~"
+code_fence = "postgresql"
+"#,
+    )
+    .unwrap();
+    fs::write(dir.path().join("template.md"), "{sections.migration}").unwrap();
+    fs::write(
+        dir.path().join("migration.sql"),
+        "--~ the users table\n--~ spec:startcode\nCREATE TABLE users ();\n--~ spec:endcode\n// not a spec comment\n",
+    )
+    .unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(
+        result,
+        "the users table\n```postgresql\nCREATE TABLE users ();\n```\n"
+    );
+}
+
+#[test]
+fn test_custom_language_overrides_builtin() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = dir.path().join("Specification.toml");
+    fs::write(
+        &spec_path,
+        r#"[metadata]
+name = "Test"
+authors = []
+
+[config]
+template = "template.md"
+
+[sections]
+module = "module.ml"
+
+[languages.ml]
+start = "(**~"
+end = "*)"
+"#,
+    )
+    .unwrap();
+    fs::write(dir.path().join("template.md"), "{sections.module}").unwrap();
+    fs::write(
+        dir.path().join("module.ml"),
+        "(*~ ignored *)\n(**~ extracted *)\n",
+    )
+    .unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(result, "extracted\n");
+}
+
+// =============================================================================
+// Rust Items Tests
+// =============================================================================
+
+const ITEMS_SOURCE: &str = r#"//~ spec:item
+/// A point
+#[derive(Debug)]
+pub struct Point {
+    x: u8,
+}
+
+impl Point {
+    //~ spec:item
+    pub fn new(x: u8) -> Self {
+        Self { x }
+    }
+}
+"#;
+
+#[test]
+fn test_rust_items() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"lib = "lib.rs""#, "{sections.lib}");
+    fs::write(dir.path().join("lib.rs"), ITEMS_SOURCE).unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(
+        result,
+        "```rs\n/// A point\n#[derive(Debug)]\npub struct Point {\n    x: u8,\n}\n```\n```rs\npub fn new(x: u8) -> Self\n```\n"
+    );
+}
+
+#[test]
+fn test_rust_item_missing_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"lib = "lib.rs""#, "{sections.lib}");
+    fs::write(dir.path().join("lib.rs"), "struct A;\n//~ spec:item\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("there are no items after this instruction"));
+}
+
+#[test]
+fn test_rust_item_outside_rust_file_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"lib = "lib.go""#, "{sections.lib}");
+    fs::write(dir.path().join("lib.go"), "//~ spec:item\nfunc f() {}\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+}
+
+// =============================================================================
+// Embedded Code Tests
+// =============================================================================
+
+const CODE_SOURCE: &str = r#"/// A point
+pub struct Point {
+    x: u8,
+}
+
+impl Point {
+    /// Creates a point
+    pub fn new(x: u8) -> Self {
+        Self { x }
+    }
+}
+"#;
+
+#[test]
+fn test_embed_code() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "The point:\n{code \"lib.rs\" \"Point\"}\nIts constructor:\n{code \"lib.rs\" \"new\"}\n",
+    );
+    fs::write(dir.path().join("lib.rs"), CODE_SOURCE).unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(
+        result,
+        "The point:\n```rs\n/// A point\npub struct Point {\n    x: u8,\n}\n```\nIts constructor:\n```rs\n/// Creates a point\npub fn new(x: u8) -> Self\n```\n"
+    );
+}
+
+#[test]
+fn test_embed_missing_code_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", "", "{code \"lib.rs\" \"Line\"}\n");
+    fs::write(dir.path().join("lib.rs"), CODE_SOURCE).unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_embed_ambiguous_code() {
+    let dir = TempDir::new().unwrap();
+    let source = format!(
+        "{CODE_SOURCE}\nimpl Line {{\n    pub fn new() -> Self {{\n        Self\n    }}\n}}\n"
+    );
+    fs::write(dir.path().join("lib.rs"), source).unwrap();
+
+    let spec_path = setup_spec(dir.path(), "", "", "{code \"lib.rs\" \"new\"}\n");
+    let output = run_cargo_spec("build", &spec_path);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("several items have this name"), "{stderr}");
+    assert!(stderr.contains("`Point::new` (line 7)"), "{stderr}");
+    assert!(stderr.contains("`Line::new` (line 14)"), "{stderr}");
+
+    let spec_path = setup_spec(dir.path(), "", "", "{code \"lib.rs\" \"Line::new\"}\n");
+    assert_eq!(build_spec(&spec_path), "```rs\npub fn new() -> Self\n```\n");
+}
+
+#[test]
+fn test_embed_code_from_non_rust_file_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", "", "{code \"lib.go\" \"Point\"}\n");
+    fs::write(dir.path().join("lib.go"), "type Point struct {}\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+}
+
+// =============================================================================
+// Template Includes Tests
+// =============================================================================
+
+#[test]
+fn test_template_includes() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"lib = "lib.rs""#,
+        "# Spec\n\n{include \"parts/intro.md\"}",
+    );
+    fs::create_dir(dir.path().join("parts")).unwrap();
+    fs::write(
+        dir.path().join("parts/intro.md"),
+        "## Intro\n\n{include \"lib.md\"}",
+    )
+    .unwrap();
+    fs::write(dir.path().join("parts/lib.md"), "{sections.lib}").unwrap();
+    fs::write(dir.path().join("lib.rs"), "//~ lib text\n").unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(result, "# Spec\n\n## Intro\n\nlib text\n");
+}
+
+#[test]
+fn test_template_include_cycle_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", "", "{include \"a.md\"}");
+    fs::write(dir.path().join("a.md"), "{include \"template.md\"}").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("this include creates a cycle"));
+}
+
+#[test]
+fn test_template_include_errors_point_at_included_file() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"lib = "lib.rs""#,
+        "# Spec\n\n{include \"part.md\"}\n{sections.lib}\n",
+    );
+    fs::write(dir.path().join("lib.rs"), "//~ text\n").unwrap();
+
+    fs::write(
+        dir.path().join("part.md"),
+        "## Part\n\n{sections.lib | shift_heading}",
+    )
+    .unwrap();
+    let output = run_cargo_spec("build", &spec_path);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("part.md:3:17"), "{stderr}");
+
+    fs::write(
+        dir.path().join("part.md"),
+        "## Part\n\n{{ if sections.lib }}",
+    )
+    .unwrap();
+    let output = run_cargo_spec("build", &spec_path);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("part.md:3:"), "{stderr}");
+}
+
+// =============================================================================
+// Section Placeholders Tests
+// =============================================================================
+
+#[test]
+fn test_missing_section_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"lib = "lib.rs""#,
+        "{sections.lib}\n{sections.other | indent}",
+    );
+    fs::write(dir.path().join("lib.rs"), "//~ text\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("section `other` is not defined"));
+}
+
+#[test]
+fn test_unused_section_warns() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "lib = \"lib.rs\"\nunused = \"lib.rs\"",
+        "{sections.lib}",
+    );
+    fs::write(dir.path().join("lib.rs"), "//~ text\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Section `unused` is not used by the template"));
+}
+
+// =============================================================================
+// Template Filters Tests
+// =============================================================================
+
+#[test]
+fn test_template_filters() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"lib = "lib.rs""#,
+        "# Spec\n\n{sections.lib | strip_heading | shift_headings 1 | details \"The lib\"}\n{sections.lib | indent 2}",
+    );
+    fs::write(
+        dir.path().join("lib.rs"),
+        "//~ # Lib\n//~\n//~ text\n//~\n//~ # Part\n",
+    )
+    .unwrap();
+
+    let result = build_spec(&spec_path);
+
+    assert_eq!(
+        result,
+        "# Spec\n\n<details>\n<summary>The lib</summary>\n\ntext\n\n## Part\n\n</details>\n\n  # Lib\n\n  text\n\n  # Part\n"
+    );
+}
+
+#[test]
+fn test_admonition_filter_with_code() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"lib = "lib.rs""#,
+        "{sections.lib | admonition warning}\nAfter\n",
+    );
+    fs::write(
+        dir.path().join("lib.rs"),
+        "//~ Careful:\n//~ spec:startcode\nfn a() {}\n//~ spec:endcode\n",
+    )
+    .unwrap();
+
+    let result = build_spec(&spec_path);
+    assert!(result.starts_with("````admonish warning\nCareful:\n```rs\nfn a() {}\n```\n"));
+    assert!(result.ends_with("````\n\nAfter\n"));
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(dir.path().join("docusaurus.md"))
+        .arg("--flavor")
+        .arg("docusaurus")
+        .output()
+        .expect("Failed to execute cargo-spec");
+    assert!(output.status.success());
+
+    let result = fs::read_to_string(dir.path().join("docusaurus.md")).unwrap();
+    assert!(result.starts_with(":::warning\nCareful:\n```rs\nfn a() {}\n```\n"));
+    assert!(result.ends_with(":::\n\nAfter\n"));
+}
+
+#[test]
+fn test_unknown_template_filter_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        r#"lib = "lib.rs""#,
+        "{sections.lib | shift_heading}",
+    );
+    fs::write(dir.path().join("lib.rs"), "//~ text\n").unwrap();
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown filter"));
+}
+
+// =============================================================================
+// Lint Tests
+// =============================================================================
+
+fn lint_spec(spec_path: &Path) -> std::process::Output {
+    Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("lint")
+        .arg("-s")
+        .arg(spec_path)
+        .output()
+        .expect("Failed to execute cargo-spec")
+}
+
+#[test]
+fn test_lint_reports_problems() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"lib = "lib.rs""#, "{sections.lib}");
+    fs::write(
+        dir.path().join("lib.rs"),
+        "//~ # Lib\n//~\n//~ ### Deep\n//~\n//~ see [a](missing.md) and [b](#nope)\n",
+    )
+    .unwrap();
+
+    let output = lint_spec(&spec_path);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Heading level jumps from 1 to 3"));
+    assert!(stderr.contains("this file doesn't exist"));
+    assert!(stderr.contains("no such anchor in the rendered specification"));
+    assert!(stderr.contains("Found 3 problems"));
+}
+
+#[test]
+fn test_lint_without_problems() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"lib = "lib.rs""#, "{sections.lib}");
+    fs::write(
+        dir.path().join("lib.rs"),
+        "//~ # Lib\n//~\n//~ ## Part\n//~\n//~ see [the lib](#lib), [the file](lib.rs) and $x$\n",
+    )
+    .unwrap();
+
+    let output = lint_spec(&spec_path);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no problems found"));
+}
+
+// =============================================================================
+// Watch Tests
+// =============================================================================
+
+#[test]
+fn test_watch_rebuilds_on_change() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"code = "lib.rs""#, "{sections.code}");
+    let lib = dir.path().join("lib.rs");
+    let output_path = dir.path().join("output.md");
+    fs::write(&lib, "//~ some spec\n").unwrap();
+
+    let mut watcher = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("watch")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(&output_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to execute cargo-spec");
+    let wait_for = |content: &str| {
+        for _ in 0..50 {
+            if fs::read_to_string(&output_path).is_ok_and(|output| output == content) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        false
+    };
+
+    // (how changes are debounced is unit tested, as it depends on timing)
+    let built = wait_for("some spec\n");
+    thread::sleep(Duration::from_millis(200));
+    fs::write(&lib, "//~ changed spec\n").unwrap();
+    let rebuilt = wait_for("changed spec\n");
+    // the summary is printed right after writing the specification
+    thread::sleep(Duration::from_millis(200));
+    watcher.kill().unwrap();
+    let output = watcher.wait_with_output().unwrap();
+
+    assert!(built);
+    assert!(rebuilt);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("=> built after changes to lib.rs ("));
+}
+
+// =============================================================================
+// Serve Tests
+// =============================================================================
+
+/// Requests a page from a local server, waiting for it to start
+fn get(port: u16, path: &str) -> String {
+    for _ in 0..50 {
+        if let Ok(mut stream) = TcpStream::connect(("127.0.0.1", port)) {
+            write!(stream, "GET {path} HTTP/1.0\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            return response;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("the server didn't start");
+}
+
+#[test]
+fn test_serve() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", r#"code = "lib.rs""#, "{sections.code}");
+    fs::write(dir.path().join("lib.rs"), "//~ some spec\n").unwrap();
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let mut server = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("serve")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("--port")
+        .arg(port.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to execute cargo-spec");
+    let page = get(port, "/");
+    let missing = get(port, "/missing.html");
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    assert!(page.starts_with("HTTP/1.0 200"));
+    assert!(page.contains("<p>some spec</p>"));
+    assert!(page.contains("new EventSource(\"/events\")"));
+    assert!(missing.starts_with("HTTP/1.0 404"));
+}
+
+#[test]
+fn test_serve_specifications_with_the_same_file_name() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = dir.path().join("Specification.toml");
+    let spec = |name: &str| {
+        format!(
+            "[[spec]]\noutput_file = \"{name}/spec.html\"\n\n[spec.metadata]\nname = \"{name}\"\nauthors = []\n\n[spec.config]\ntemplate = \"{name}.md\"\n\n[spec.sections]\n\n"
+        )
+    };
+    fs::write(&spec_path, format!("{}{}", spec("a"), spec("b"))).unwrap();
+    fs::write(dir.path().join("a.md"), "spec a\n").unwrap();
+    fs::write(dir.path().join("b.md"), "spec b\n").unwrap();
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let mut server = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("serve")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("--port")
+        .arg(port.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to execute cargo-spec");
+    let index = get(port, "/");
+    let a = get(port, "/a/spec.html");
+    let b = get(port, "/b/spec.html");
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    assert!(index.contains("<a href=\"/a/spec.html\">a/spec.html</a>"));
+    assert!(a.contains("<p>spec a</p>"));
+    assert!(b.contains("<p>spec b</p>"));
+}
+
+// =============================================================================
+// HTML Output Tests
+// =============================================================================
+
+#[test]
+fn test_html_output_is_standalone() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "# Title\n\n## A section\n\n```rs\nlet x = 1;\n```\n",
+    );
+    let output_path = dir.path().join("output.html");
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--output-format")
+        .arg("html")
+        .output()
+        .expect("Failed to execute cargo-spec");
+    assert!(output.status.success());
+
+    let result = fs::read_to_string(output_path).unwrap();
+    assert!(result.contains("<style>"));
+    assert!(result.contains(r##"<a href="#a-section">A section</a>"##));
+    assert!(result.contains(r#"<pre lang="rs" style="#));
+    assert!(!result.contains("<script"));
+    assert!(!result.contains("<link"));
+}
+
+// =============================================================================
+// PDF Output Tests
+// =============================================================================
+
+#[test]
+fn test_pdf_output() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "# Title\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n```rs\nlet x = 1;\n```\n\n$x^2$\n",
+    );
+    let output_path = dir.path().join("output.pdf");
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--output-format")
+        .arg("pdf")
+        .output()
+        .expect("Failed to execute cargo-spec");
+    assert!(output.status.success());
+
+    let result = fs::read(output_path).unwrap();
+    assert!(result.starts_with(b"%PDF-"));
+}
+
+#[test]
+fn test_check_pdf_output() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(dir.path(), "", "", "# Title\n\nSome text.\n");
+    let output_path = dir.path().join("output.pdf");
+    let run = |command: &str| {
+        Command::new(cargo_spec_binary())
+            .arg("spec")
+            .arg(command)
+            .arg("-s")
+            .arg(&spec_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--output-format")
+            .arg("pdf")
+            .output()
+            .expect("Failed to execute cargo-spec")
+    };
+    assert!(run("build").status.success());
+
+    // the same specification always gives the same document
+    thread::sleep(Duration::from_millis(1100));
+    assert!(run("check").status.success());
+
+    fs::write(dir.path().join("template.md"), "# Title\n\nOther text.\n").unwrap();
+    let output = run("check");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("differs from the rebuilt specification (binary output)"));
+    assert!(!stdout.contains("@@"));
+}
+
+// =============================================================================
+// LaTeX Output Tests
+// =============================================================================
+
+#[test]
+fn test_latex_output() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "# Intro\n\nSome_text and $x_1^2$.\n\n```rs\nlet x = 1;\n```\n",
+    );
+    let output_path = dir.path().join("output.tex");
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--output-format")
+        .arg("latex")
+        .output()
+        .expect("Failed to execute cargo-spec");
+    assert!(output.status.success());
+
+    let result = fs::read_to_string(output_path).unwrap();
+    assert!(result.contains("\\title{Test}\n\\author{Test Author}"));
+    assert!(result.contains("\\section{Intro}\n\nSome\\_text and $x_1^2$.\n"));
+    assert!(result.contains("\\begin{lstlisting}[language=Rust]\nlet x = 1;\n\\end{lstlisting}"));
+    assert!(result.ends_with("\\end{document}\n"));
+}
+
+// =============================================================================
+// Book Output Tests
+// =============================================================================
+
+#[test]
+fn test_mdbook_output() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "# Spec\n\nintro\n\n## First part\n\none\n\n### Details\n\n## Second part\n\ntwo\n",
+    );
+    let output_path = dir.path().join("book");
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--output-format")
+        .arg("book")
+        .output()
+        .expect("Failed to execute cargo-spec");
+    assert!(output.status.success());
+
+    let read = |path: &str| fs::read_to_string(output_path.join(path)).unwrap();
+    assert_eq!(
+        read("src/SUMMARY.md"),
+        "# Summary\n\n[Introduction](README.md)\n\n- [First part](first-part.md)\n- [Second part](second-part.md)\n"
+    );
+    assert_eq!(read("src/README.md"), "# Spec\n\nintro\n\n");
+    assert_eq!(
+        read("src/first-part.md"),
+        "# First part\n\none\n\n## Details\n\n"
+    );
+    assert_eq!(read("src/second-part.md"), "# Second part\n\ntwo\n");
+    assert!(read("book.toml").contains("title = \"Test\""));
+}
+
+#[test]
+fn test_book_removes_stale_chapters() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "# Spec\n\n## First part\n\n## Second part\n",
+    );
+    let output_path = dir.path().join("book");
+    let run = |command: &str| {
+        Command::new(cargo_spec_binary())
+            .arg("spec")
+            .arg(command)
+            .arg("-s")
+            .arg(&spec_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--output-format")
+            .arg("book")
+            .output()
+            .expect("Failed to execute cargo-spec")
+    };
+    assert!(run("build").status.success());
+    fs::create_dir(output_path.join("theme")).unwrap();
+    fs::write(output_path.join("theme/custom.css"), "").unwrap();
+    fs::write(output_path.join("src/notes.md"), "# Notes\n").unwrap();
+    fs::write(output_path.join("extra.toml"), "").unwrap();
+
+    // a renamed chapter leaves its old page behind, until the next build
+    fs::write(
+        dir.path().join("template.md"),
+        "# Spec\n\n## First part\n\n## Renamed part\n",
+    )
+    .unwrap();
+    fs::write(output_path.join("src/renamed-part.md"), "# Renamed part\n").unwrap();
+    fs::write(
+        output_path.join("src/SUMMARY.md"),
+        "# Summary\n\n[Introduction](README.md)\n\n- [First part](first-part.md)\n- [Renamed part](renamed-part.md)\n",
+    )
+    .unwrap();
+    let output = run("check");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("second-part.md is not part of the rebuilt specification anymore"));
+
+    assert!(run("build").status.success());
+    assert!(!output_path.join("src/second-part.md").exists());
+    assert!(output_path.join("src/renamed-part.md").exists());
+    assert!(output_path.join("theme/custom.css").exists());
+    assert!(output_path.join("src/notes.md").exists());
+    assert!(output_path.join("extra.toml").exists());
+    assert!(run("check").status.success());
+}
+
+#[test]
+fn test_book_keeps_files_it_did_not_generate() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "# Spec\n\n## First part\n\n## Second part\n",
+    );
+    fs::write(dir.path().join("handwritten.md"), "# Mine\n").unwrap();
+    fs::write(dir.path().join("other.json"), "{}").unwrap();
+
+    // the book is written next to the specification file, in the docusaurus flavor
+    for _ in 0..2 {
+        let output = Command::new(cargo_spec_binary())
+            .arg("spec")
+            .arg("build")
+            .arg("-s")
+            .arg(&spec_path)
+            .arg("-o")
+            .arg(dir.path())
+            .arg("--output-format")
+            .arg("book")
+            .arg("-F")
+            .arg("docusaurus")
+            .output()
+            .expect("Failed to execute cargo-spec");
+        assert!(output.status.success());
+    }
+
+    assert!(dir.path().join("first-part.md").exists());
+    assert!(spec_path.exists());
+    assert!(dir.path().join("template.md").exists());
+    assert!(dir.path().join("handwritten.md").exists());
+    assert!(dir.path().join("other.json").exists());
+}
+
+#[test]
+fn test_docusaurus_docs_output() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_spec(
+        dir.path(),
+        "",
+        "",
+        "# Spec\n\nintro\n\n## First part\n\none\n\n## Second part\n\ntwo\n",
+    );
+    let output_path = dir.path().join("docs");
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--output-format")
+        .arg("book")
+        .arg("--flavor")
+        .arg("docusaurus")
+        .output()
+        .expect("Failed to execute cargo-spec");
+    assert!(output.status.success());
+
+    let read = |path: &str| fs::read_to_string(output_path.join(path)).unwrap();
+    assert_eq!(
+        read("_category_.json"),
+        "{\n  \"label\": \"Test\",\n  \"link\": {\n    \"type\": \"generated-index\"\n  }\n}\n"
+    );
+    assert_eq!(
+        read("introduction.md"),
+        "---\ntitle: \"Test\"\nsidebar_position: 1\nslug: introduction\n---\n\n# Spec\n\nintro\n\n"
+    );
+    assert_eq!(
+        read("second-part.md"),
+        "---\ntitle: \"Second part\"\nsidebar_position: 3\nslug: second-part\n---\n\n# Second part\n\ntwo\n"
+    );
+}
+
+// =============================================================================
+// Multiple Specifications Tests
+// =============================================================================
+
+const MULTIPLE_SPECS: &str = r#"[[spec]]
+output_file = "consensus.md"
+
+[spec.metadata]
+name = "Consensus"
+authors = []
+
+[spec.config]
+template = "consensus.md.tpl"
+
+[spec.sections]
+shared = "shared.rs"
+
+[[spec]]
+
+[spec.metadata]
+name = "Wire Format"
+authors = []
+
+[spec.config]
+template = "wire.md.tpl"
+
+[spec.sections]
+shared = "shared.rs"
+wire = "wire.rs"
+"#;
+
+fn setup_multiple_specs(dir: &Path) -> PathBuf {
+    let spec_path = dir.join("Specification.toml");
+    fs::write(&spec_path, MULTIPLE_SPECS).unwrap();
+    fs::write(
+        dir.join("consensus.md.tpl"),
+        "# Consensus\n\n{sections.shared}",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("wire.md.tpl"),
+        "# Wire\n\n{sections.shared}{sections.wire}",
+    )
+    .unwrap();
+    fs::write(dir.join("shared.rs"), "//~ shared text\n").unwrap();
+    fs::write(dir.join("wire.rs"), "//~ wire text\n").unwrap();
+    spec_path
+}
+
+fn run_without_output_file(command: &str, spec_path: &Path) -> std::process::Output {
+    Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg(command)
+        .arg("-s")
+        .arg(spec_path)
+        .output()
+        .expect("Failed to execute cargo-spec")
+}
+
+#[test]
+fn test_multiple_specs() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_multiple_specs(dir.path());
+
+    let output = run_without_output_file("build", &spec_path);
+    assert!(output.status.success());
+
+    // the first spec has an explicit output file, the second one is named after the spec
+    assert_eq!(
+        fs::read_to_string(dir.path().join("consensus.md")).unwrap(),
+        "# Consensus\n\nshared text\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("wire-format.md")).unwrap(),
+        "# Wire\n\nshared text\nwire text\n"
+    );
+
+    let output = run_without_output_file("check", &spec_path);
+    assert!(output.status.success());
+
+    fs::write(dir.path().join("wire.rs"), "//~ new wire text\n").unwrap();
+    let output = run_without_output_file("check", &spec_path);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("+new wire text"));
+}
+
+#[test]
+fn test_multiple_specs_with_output_file_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_multiple_specs(dir.path());
+
+    let output = run_cargo_spec("build", &spec_path);
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_multiple_specs_with_same_output_file_fails() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = setup_multiple_specs(dir.path());
+    fs::write(
+        &spec_path,
+        MULTIPLE_SPECS.replace("consensus.md", "wire-format.md"),
+    )
+    .unwrap();
+
+    let output = run_without_output_file("build", &spec_path);
+
+    assert!(!output.status.success());
+}
+
+// =============================================================================
+// Workspace Tests
+// =============================================================================
+
+/// Creates a workspace with a member with a specification (`alpha`)
+/// and a member without one (`beta`)
+fn setup_workspace(dir: &Path) {
+    fs::write(
+        dir.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"crates/*\"]\n",
+    )
+    .unwrap();
+
+    let alpha = dir.join("crates/alpha");
+    fs::create_dir_all(&alpha).unwrap();
+    fs::write(
+        alpha.join("Cargo.toml"),
+        "[package]\nname = \"alpha\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    setup_spec(&alpha, "", r#"lib = "lib.rs""#, "{sections.lib}");
+    fs::write(alpha.join("lib.rs"), "//~ alpha spec\n").unwrap();
+
+    let beta = dir.join("crates/beta");
+    fs::create_dir_all(&beta).unwrap();
+    fs::write(
+        beta.join("Cargo.toml"),
+        "[package]\nname = \"beta\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+}
+
+fn run_on_workspace(command: &str, dir: &Path) -> std::process::Output {
+    Command::new(cargo_spec_binary())
+        .current_dir(dir)
+        .arg("spec")
+        .arg(command)
+        .arg("--workspace")
+        .output()
+        .expect("Failed to execute cargo-spec")
+}
+
+#[test]
+fn test_workspace_build() {
+    let dir = TempDir::new().unwrap();
+    setup_workspace(dir.path());
+
+    let output = run_on_workspace("build", dir.path());
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("=> alpha: ok"));
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("crates/alpha/specification.md")).unwrap(),
+        "alpha spec\n"
+    );
+    assert!(!dir.path().join("crates/beta/specification.md").exists());
+
+    let output = run_on_workspace("check", dir.path());
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_workspace_build_reports_failed_members() {
+    let dir = TempDir::new().unwrap();
+    setup_workspace(dir.path());
+    let gamma = dir.path().join("crates/gamma");
+    fs::create_dir_all(&gamma).unwrap();
+    setup_spec(&gamma, "", r#"lib = "missing.rs""#, "{sections.lib}");
+
+    let output = run_on_workspace("build", dir.path());
+    assert!(!output.status.success());
+
+    // the other members are still built
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("=> alpha: ok"));
+    assert!(stderr.contains("=> gamma: failed"));
+
+    // and the error of the failed member is reported on stderr too
+    assert!(stderr.contains("missing.rs"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("missing.rs"));
+    assert!(dir.path().join("crates/alpha/specification.md").exists());
+}
+
+#[test]
+fn test_workspace_build_to_stdout_fails() {
+    let dir = TempDir::new().unwrap();
+    setup_workspace(dir.path());
+
+    let output = Command::new(cargo_spec_binary())
+        .current_dir(dir.path())
+        .args(["spec", "build", "--workspace", "-o", "-"])
+        .output()
+        .expect("Failed to execute cargo-spec");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("`cargo spec build --workspace` can't use stdout"));
+    assert!(!dir.path().join("crates/alpha/-").exists());
+}
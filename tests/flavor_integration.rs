@@ -874,6 +874,72 @@ Warning text.
     assert!(!result.contains(":::warning"));
 }
 
+#[test]
+fn test_config_file_flavor_per_spec() {
+    let dir = TempDir::new().unwrap();
+    let spec_path = dir.path().join("Specification.toml");
+    fs::write(
+        &spec_path,
+        r#"[output]
+flavor = "docusaurus"
+
+[[spec]]
+output_file = "shared.md"
+
+[spec.metadata]
+name = "Shared"
+authors = []
+
+[spec.config]
+template = "template.md"
+
+[spec.sections]
+
+[[spec]]
+output_file = "own.md"
+
+[spec.metadata]
+name = "Own"
+authors = []
+
+[spec.config]
+template = "template.md"
+
+[spec.output]
+flavor = "mdbook"
+
+[spec.sections]
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("template.md"),
+        "# Test\n\n```admonish warning\nWarning text.\n```\n",
+    )
+    .unwrap();
+
+    let build = |flavor: Option<&str>| {
+        let mut cmd = Command::new(cargo_spec_binary());
+        cmd.arg("spec").arg("build").arg("-s").arg(&spec_path);
+        if let Some(flavor) = flavor {
+            cmd.arg("--flavor").arg(flavor);
+        }
+        assert!(cmd.output().unwrap().status.success());
+        let read = |file: &str| fs::read_to_string(dir.path().join(file)).unwrap();
+        (read("shared.md"), read("own.md"))
+    };
+
+    // each specification can override the shared flavor
+    let (shared, own) = build(None);
+    assert!(shared.contains(":::warning"));
+    assert!(own.contains("```admonish warning"));
+
+    // and the CLI overrides both
+    let (shared, own) = build(Some("docusaurus"));
+    assert!(shared.contains(":::warning"));
+    assert!(own.contains(":::warning"));
+}
+
 // =============================================================================
 // Combined Transformation Tests
 // =============================================================================
@@ -0,0 +1,269 @@
+use std::{fs, path::PathBuf, process::Command};
+use tempfile::TempDir;
+
+fn cargo_spec_binary() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("target/debug/cargo-spec");
+    path
+}
+
+/// Builds a specification made of a single section extracted from `source`,
+/// saved as a file with the given extension, and returns the result
+fn extract(extension: &str, source: &str) -> String {
+    let dir = TempDir::new().unwrap();
+    let spec_path = dir.path().join("Specification.toml");
+    let output_path = dir.path().join("output.md");
+
+    fs::write(
+        &spec_path,
+        format!(
+            r#"[metadata]
+name = "Test"
+authors = ["Test Author"]
+
+[config]
+template = "template.md"
+
+[sections]
+source = "source.{extension}"
+"#
+        ),
+    )
+    .unwrap();
+    fs::write(dir.path().join("template.md"), "{sections.source}").unwrap();
+    fs::write(dir.path().join(format!("source.{extension}")), source).unwrap();
+
+    let output = Command::new(cargo_spec_binary())
+        .arg("spec")
+        .arg("build")
+        .arg("-s")
+        .arg(&spec_path)
+        .arg("-o")
+        .arg(&output_path)
+        .output()
+        .expect("Failed to execute cargo-spec");
+
+    if !output.status.success() {
+        panic!(
+            "cargo-spec failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    fs::read_to_string(output_path).expect("Failed to read output file")
+}
+
+// =============================================================================
+// `#` Comments Tests
+// =============================================================================
+
+#[test]
+fn test_python() {
+    let result = extract(
+        "py",
+        "#~ some spec\n# a normal comment\n#~ spec:startcode\ndef f(): pass\n#~ spec:endcode\n",
+    );
+
+    assert_eq!(result, "some spec\n```python\ndef f(): pass\n```\n");
+}
+
+#[test]
+fn test_shell() {
+    let result = extract("sh", "#!/bin/sh\n#~ some spec\necho hello\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+#[test]
+fn test_toml() {
+    let result = extract(
+        "toml",
+        "#~ the config\n#~ spec:startcode\n[server]\nport = 80\n#~ spec:endcode\n",
+    );
+
+    assert_eq!(result, "the config\n```toml\n[server]\nport = 80\n```\n");
+}
+
+#[test]
+fn test_yaml() {
+    let result = extract(
+        "yml",
+        "#~ some spec\n#~ spec:startcode\nkey: value\n#~ spec:endcode\n",
+    );
+
+    assert_eq!(result, "some spec\n```yaml\nkey: value\n```\n");
+}
+
+// =============================================================================
+// `--` Comments Tests
+// =============================================================================
+
+#[test]
+fn test_haskell() {
+    let result = extract(
+        "hs",
+        "--~ some spec\n-- a normal comment\n--~ spec:startcode\nmain = pure ()\n--~ spec:endcode\n",
+    );
+
+    assert_eq!(result, "some spec\n```haskell\nmain = pure ()\n```\n");
+}
+
+#[test]
+fn test_sql() {
+    let result = extract("sql", "--~ the users table\nCREATE TABLE users ();\n");
+
+    assert_eq!(result, "the users table\n");
+}
+
+#[test]
+fn test_lua() {
+    let result = extract("lua", "--~ some spec\nlocal x = 1\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+// =============================================================================
+// `;` and `%` Comments Tests
+// =============================================================================
+
+#[test]
+fn test_lisp() {
+    let result = extract("lisp", ";~ some spec\n(defun f () nil)\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+#[test]
+fn test_erlang() {
+    let result = extract(
+        "erl",
+        "%~ some spec\n%~ spec:startcode\nf() -> ok.\n%~ spec:endcode\n",
+    );
+
+    assert_eq!(result, "some spec\n```erlang\nf() -> ok.\n```\n");
+}
+
+#[test]
+fn test_latex() {
+    let result = extract("tex", "%~ some spec\n\\section{Intro}\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+// =============================================================================
+// Block Comments Tests
+// =============================================================================
+
+#[test]
+fn test_ocaml() {
+    let result = extract("ml", "(*~ some spec *)\nlet x = 1\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+#[test]
+fn test_html_single_line() {
+    let result = extract("html", "<!--~ some spec -->\n<p>hello</p>\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+#[test]
+fn test_html_multi_line() {
+    let result = extract(
+        "html",
+        "<!--~ some spec\n      on several lines\n-->\n<p>hello</p>\n",
+    );
+
+    assert_eq!(result, "some spec\non several lines\n\n");
+}
+
+#[test]
+fn test_xml() {
+    let result = extract("xml", "<!--~ some spec -->\n<root/>\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+// =============================================================================
+// Fallback Tests
+// =============================================================================
+
+#[test]
+fn test_unknown_extension_uses_double_slash() {
+    let result = extract(
+        "go",
+        "//~ some spec\n// a normal comment\n//~ spec:startcode\nfunc f() {}\n//~ spec:endcode\n",
+    );
+
+    assert_eq!(result, "some spec\n```go\nfunc f() {}\n```\n");
+}
+
+// =============================================================================
+// C-family Block Comments Tests
+// =============================================================================
+
+#[test]
+fn test_c_block_comment_single_line() {
+    let result = extract("c", "/*~ some spec */\nint x = 1;\n");
+
+    assert_eq!(result, "some spec\n");
+}
+
+#[test]
+fn test_c_block_comment_multi_line() {
+    let result = extract(
+        "c",
+        "int x = 1;\n  /*~ some spec\n      - a list\n        - nested\n  */\nint y = 2;\n",
+    );
+
+    assert_eq!(result, "some spec\n- a list\n  - nested\n\n");
+}
+
+#[test]
+fn test_rust_doc_block_comment() {
+    let result = extract(
+        "rs",
+        "/**~ some spec\n    on two lines */\nstruct A;\n//~ and a line comment\n",
+    );
+
+    assert_eq!(result, "some spec\non two lines\nand a line comment\n");
+}
+
+#[test]
+fn test_star_block_comment() {
+    let result = extract(
+        "rs",
+        "/**~ some spec\n * with a star\n *\n *   - indented\n */\nstruct A;\n",
+    );
+
+    assert_eq!(result, "some spec\nwith a star\n\n  - indented\n\n");
+
+    let result = extract("c", "  /*~ some spec\n   * on two lines */\nint x = 1;\n");
+
+    assert_eq!(result, "some spec\non two lines\n");
+}
+
+#[test]
+fn test_block_comment_with_instructions() {
+    let result = extract(
+        "go",
+        "/*~ spec:startcode */\nfunc f() {}\n/*~ spec:endcode */\n",
+    );
+
+    assert_eq!(result, "```go\nfunc f() {}\n```\n");
+}
+
+#[test]
+fn test_haskell_block_comment() {
+    let result = extract("hs", "{-~ some spec\n   more spec -}\n--~ and a line\n");
+
+    assert_eq!(result, "some spec\nmore spec\nand a line\n");
+}
+
+#[test]
+fn test_lua_block_comment() {
+    let result = extract("lua", "--[[~ some spec ]]\nlocal x = 1\n");
+
+    assert_eq!(result, "some spec\n");
+}
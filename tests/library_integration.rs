@@ -0,0 +1,199 @@
+use cargo_spec::{
+    build::{build, check, Difference},
+    lint::lint,
+    toml_parser::{Config, Metadata},
+    Content, OutputFormat, SpecBuilder, Specification,
+};
+use std::{collections::HashMap, fs, path::Path};
+use tempfile::TempDir;
+
+fn setup_spec(dir: &Path) {
+    fs::write(
+        dir.join("Specification.toml"),
+        r#"[metadata]
+name = "Test"
+authors = ["Test Author"]
+
+[config]
+template = "template.md"
+
+[sections]
+lib = "lib.rs"
+"#,
+    )
+    .unwrap();
+    fs::write(dir.join("template.md"), "# Spec\n\n{sections.lib}\n").unwrap();
+    fs::write(
+        dir.join("lib.rs"),
+        "//~ some spec\nfn main() {}\n//~ more spec\n",
+    )
+    .unwrap();
+}
+
+fn markdown(content: Content) -> String {
+    match content {
+        Content::File(content) => String::from_utf8(content).unwrap(),
+        Content::Directory(_) => panic!("expected a single file"),
+    }
+}
+
+#[test]
+fn test_render_from_file() {
+    let dir = TempDir::new().unwrap();
+    setup_spec(dir.path());
+
+    let builder = SpecBuilder::from_file(dir.path().join("Specification.toml")).unwrap();
+    let content = builder.render().unwrap();
+
+    assert_eq!(markdown(content), "# Spec\n\nsome spec\nmore spec\n\n");
+    assert!(!dir.path().join("specification.md").exists());
+}
+
+#[test]
+fn test_render_specification() {
+    let dir = TempDir::new().unwrap();
+    setup_spec(dir.path());
+
+    let specification = Specification {
+        metadata: Metadata {
+            name: "Test".to_string(),
+            description: None,
+            version: None,
+            authors: vec![],
+        },
+        config: Config {
+            template: "template.md".to_string(),
+            source_links: None,
+        },
+        output: None,
+        sections: HashMap::from([("lib".to_string(), "lib.rs".to_string())]),
+        languages: HashMap::new(),
+    };
+    let builder = SpecBuilder::new(specification, dir.path()).output_format(OutputFormat::Html);
+
+    let Content::File(html) = builder.render().unwrap() else {
+        panic!("expected a single file");
+    };
+    assert!(String::from_utf8_lossy(&html).contains("some spec"));
+}
+
+#[test]
+fn test_render_with_warnings() {
+    let dir = TempDir::new().unwrap();
+    setup_spec(dir.path());
+    fs::write(dir.path().join("template.md"), "# Spec\n").unwrap();
+
+    let builder = SpecBuilder::from_file(dir.path().join("Specification.toml")).unwrap();
+    let (content, warnings) = builder.render_with_warnings().unwrap();
+
+    assert_eq!(markdown(content), "# Spec\n");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].to_string(),
+        "Section `lib` is not used by the template"
+    );
+}
+
+#[test]
+fn test_write_to() {
+    let dir = TempDir::new().unwrap();
+    setup_spec(dir.path());
+
+    let builder = SpecBuilder::from_file(dir.path().join("Specification.toml")).unwrap();
+    let mut output = vec![];
+    builder.write_to(&mut output).unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "# Spec\n\nsome spec\nmore spec\n\n"
+    );
+    assert!(builder
+        .output_format(OutputFormat::Book)
+        .write_to(vec![])
+        .is_err());
+}
+
+#[test]
+fn test_extract_file() {
+    let dir = TempDir::new().unwrap();
+    setup_spec(dir.path());
+
+    let builder = SpecBuilder::from_file(dir.path().join("Specification.toml")).unwrap();
+    let blocks = builder.extract("lib.rs").unwrap();
+
+    let blocks: Vec<_> = blocks
+        .iter()
+        .map(|block| (block.line, block.content.as_str()))
+        .collect();
+    assert_eq!(blocks, vec![(1, "some spec\n"), (3, "more spec\n")]);
+}
+
+#[test]
+fn test_several_specifications_fail() {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("Specification.toml"),
+        r#"[[spec]]
+metadata = { name = "A", authors = [] }
+config = { template = "template.md" }
+sections = {}
+"#,
+    )
+    .unwrap();
+
+    let result = SpecBuilder::from_file(dir.path().join("Specification.toml"));
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_check_returns_the_differences() {
+    let dir = TempDir::new().unwrap();
+    setup_spec(dir.path());
+    let toml_spec = dir.path().join("Specification.toml");
+    let output_file = dir.path().join("specification.md");
+
+    let built = build(
+        toml_spec.clone(),
+        Some(output_file.clone()),
+        OutputFormat::Markdown,
+        None,
+    )
+    .unwrap();
+    assert_eq!(built.output_files, vec![output_file.clone()]);
+
+    let checked = check(
+        toml_spec.clone(),
+        Some(output_file.clone()),
+        OutputFormat::Markdown,
+        None,
+    )
+    .unwrap();
+    assert_eq!(checked.output_files, vec![(output_file.clone(), vec![])]);
+    assert!(checked.up_to_date().is_ok());
+
+    fs::write(dir.path().join("lib.rs"), "//~ other spec\n").unwrap();
+    let checked = check(
+        toml_spec,
+        Some(output_file.clone()),
+        OutputFormat::Markdown,
+        None,
+    )
+    .unwrap();
+    let (_, differences) = &checked.output_files[0];
+    assert!(matches!(&differences[..], [Difference::Diff(path, diff)]
+        if *path == output_file && diff.contains("+other spec")));
+    assert!(checked.up_to_date().is_err());
+}
+
+#[test]
+fn test_lint_returns_the_problems() {
+    let dir = TempDir::new().unwrap();
+    setup_spec(dir.path());
+    fs::write(dir.path().join("lib.rs"), "//~ [broken](missing.md)\n").unwrap();
+
+    let linted = lint(dir.path().join("Specification.toml")).unwrap();
+
+    assert_eq!(linted.problems.len(), 1);
+    assert!(linted.no_problems().is_err());
+}